@@ -42,6 +42,8 @@ fn main() {
         .file("c/crf/crf1d_tag.c")
         .file("c/crf/crfsuite_train.c")
         .file("c/crf/crfsuite.c")
+        // shim
+        .file("shim/logging.c")
         .flag_if_supported("-mmacosx-version-min=10.11")
         .compile("libcrfsuite.a");
 
@@ -51,7 +53,11 @@ fn main() {
     dinghy_build::dinghy_bindgen!()
         .size_t_is_usize(false)
         .clang_arg("-v")
+        .clang_arg("-Ic/include")
         .header("c/include/crfsuite.h")
+        // crf1d.h gives access to the model internals (features, references) that are not
+        // exposed through the vtables of crfsuite.h
+        .header("c/crf/crf1d.h")
         .generate()
         .unwrap()
         .write_to_file(&p)
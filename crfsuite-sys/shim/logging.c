@@ -0,0 +1,14 @@
+#include <stdarg.h>
+#include <stdio.h>
+
+#include <crfsuite.h>
+
+/* va_list has no portable representation in Rust, the messages of the trainers are printed here */
+static int log_to_stdout(void *instance, const char *format, va_list args)
+{
+    vfprintf(stdout, format, args);
+    fflush(stdout);
+    return 0;
+}
+
+crfsuite_logging_callback crfsuite_rs_stdout_logging = log_to_stdout;
@@ -5,3 +5,13 @@
 #![allow(dead_code)]
 #![allow(improper_ctypes)]
 include!(concat!(env!("OUT_DIR"), "/crfsuite.rs"));
+
+// lookup3.c has no header, its hash function is the one used by the cqdb dictionaries
+extern "C" {
+    pub fn hashlittle(key: *const ::std::os::raw::c_void, length: usize, initval: u32) -> u32;
+}
+
+// shim/logging.c prints the messages of the trainers on the standard output
+extern "C" {
+    pub static crfsuite_rs_stdout_logging: crfsuite_logging_callback;
+}
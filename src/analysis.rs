@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use anyhow::{bail, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Accumulates reference and predicted label sequences to find the errors of a model, so that
+/// the data to relabel can be prioritised
+#[derive(Debug, Clone, Default)]
+pub struct ErrorAnalysis {
+    confusions: BTreeMap<(String, String), usize>,
+    sequences: Vec<SequenceError>,
+}
+
+/// Number of times a reference label was predicted as another one
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfusedPair {
+    pub reference: String,
+    pub predicted: String,
+    pub count: usize,
+}
+
+/// Accumulated sequence, identified by its position in the accumulation order
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceError {
+    pub index: usize,
+    /// Probability of the predicted labels according to the model
+    pub probability: f64,
+    pub num_items: usize,
+    /// Number of items whose predicted label differs from the reference
+    pub num_errors: usize,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// Labels found in the reference or the predictions, sorted
+    pub labels: Vec<String>,
+    /// Confusion matrix, `matrix[i][j]` being the number of items labeled `labels[i]` in the
+    /// reference and predicted as `labels[j]`
+    pub matrix: Vec<Vec<usize>>,
+    /// Pairs of different labels, most frequently confused first
+    pub confused_pairs: Vec<ConfusedPair>,
+    /// Sequences with the lowest probabilities first
+    pub worst_sequences: Vec<SequenceError>,
+}
+
+impl ErrorAnalysis {
+    pub fn new() -> ErrorAnalysis {
+        ErrorAnalysis::default()
+    }
+
+    /// Adds the labels of an instance along with the probability of the prediction, as given by
+    /// `Tagger::probability`
+    pub fn accumulate<S: AsRef<str>, T: AsRef<str>>(
+        &mut self,
+        reference: &[S],
+        prediction: &[T],
+        probability: f64,
+    ) -> Result<()> {
+        if reference.len() != prediction.len() {
+            bail!(
+                "reference has {} labels but prediction has {}",
+                reference.len(),
+                prediction.len()
+            )
+        }
+        let mut num_errors = 0;
+        for (r, p) in reference.iter().zip(prediction) {
+            let (r, p) = (r.as_ref(), p.as_ref());
+            if r != p {
+                num_errors += 1;
+            }
+            *self
+                .confusions
+                .entry((r.to_string(), p.to_string()))
+                .or_insert(0) += 1;
+        }
+        self.sequences.push(SequenceError {
+            index: self.sequences.len(),
+            probability,
+            num_items: reference.len(),
+            num_errors,
+        });
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.confusions.clear();
+        self.sequences.clear();
+    }
+
+    /// Makes a report keeping at most `num_sequences` of the worst sequences
+    pub fn report(&self, num_sequences: usize) -> ErrorReport {
+        let labels = self
+            .confusions
+            .keys()
+            .flat_map(|(r, p)| vec![r, p])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let index = labels
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (l.as_str(), i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut matrix = vec![vec![0; labels.len()]; labels.len()];
+        for ((r, p), count) in &self.confusions {
+            matrix[index[r.as_str()]][index[p.as_str()]] = *count;
+        }
+
+        let mut confused_pairs = self
+            .confusions
+            .iter()
+            .filter(|((r, p), _)| r != p)
+            .map(|((r, p), count)| ConfusedPair {
+                reference: r.clone(),
+                predicted: p.clone(),
+                count: *count,
+            })
+            .collect::<Vec<_>>();
+        // the sort is stable, ties staying sorted by labels
+        confused_pairs.sort_by_key(|pair| std::cmp::Reverse(pair.count));
+
+        let mut worst_sequences = self.sequences.clone();
+        worst_sequences.sort_by(|a, b| {
+            a.probability
+                .partial_cmp(&b.probability)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.num_errors.cmp(&a.num_errors))
+        });
+        worst_sequences.truncate(num_sequences);
+
+        ErrorReport {
+            labels,
+            matrix,
+            confused_pairs,
+            worst_sequences,
+        }
+    }
+}
+
+impl ErrorReport {
+    /// Writes the confusion matrix as CSV, with the reference labels as rows and the predicted
+    /// ones as columns
+    pub fn write_matrix_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        write!(writer, "reference\\predicted")?;
+        for label in &self.labels {
+            write!(writer, ",{}", csv_field(label))?;
+        }
+        writeln!(writer)?;
+        for (label, row) in self.labels.iter().zip(&self.matrix) {
+            write!(writer, "{}", csv_field(label))?;
+            for count in row {
+                write!(writer, ",{}", count)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_confused_pairs_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "reference,predicted,count")?;
+        for pair in &self.confused_pairs {
+            writeln!(
+                writer,
+                "{},{},{}",
+                csv_field(&pair.reference),
+                csv_field(&pair.predicted),
+                pair.count
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_worst_sequences_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "index,probability,num_items,num_errors")?;
+        for sequence in &self.worst_sequences {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                sequence.index, sequence.probability, sequence.num_items, sequence.num_errors
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole report as a JSON object with the fields of `ErrorReport`
+    #[cfg(feature = "serde")]
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+/// Quotes a field containing a separator, a quote or a line break, as RFC 4180 does
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfusedPair, ErrorAnalysis};
+    use crate::tests::file_path;
+    use crate::Tagger;
+
+    fn analysis() -> ErrorAnalysis {
+        let mut analysis = ErrorAnalysis::new();
+        analysis
+            .accumulate(&["O", "B-city", "I-city"], &["O", "B-city", "I-city"], 0.9)
+            .unwrap();
+        analysis
+            .accumulate(&["B-city", "O", "B-a,b"], &["O", "O", "B-city"], 0.2)
+            .unwrap();
+        analysis
+            .accumulate(&["B-city", "O"], &["O", "O"], 0.5)
+            .unwrap();
+        analysis
+    }
+
+    #[test]
+    fn error_analysis_works() {
+        let mut analysis = analysis();
+        assert!(analysis.accumulate(&["O"], &["O", "O"], 0.5).is_err());
+
+        let report = analysis.report(2);
+        assert_eq!(report.labels, vec!["B-a,b", "B-city", "I-city", "O"]);
+        assert_eq!(
+            report.matrix,
+            vec![
+                vec![0, 1, 0, 0],
+                vec![0, 1, 0, 2],
+                vec![0, 0, 1, 0],
+                vec![0, 0, 0, 3]
+            ]
+        );
+        assert_eq!(
+            report.confused_pairs,
+            vec![
+                ConfusedPair {
+                    reference: "B-city".to_string(),
+                    predicted: "O".to_string(),
+                    count: 2,
+                },
+                ConfusedPair {
+                    reference: "B-a,b".to_string(),
+                    predicted: "B-city".to_string(),
+                    count: 1,
+                }
+            ]
+        );
+        let worst = report
+            .worst_sequences
+            .iter()
+            .map(|s| (s.index, s.num_errors))
+            .collect::<Vec<_>>();
+        assert_eq!(worst, vec![(1, 2), (2, 1)]);
+
+        analysis.clear();
+        assert!(analysis.report(2).labels.is_empty());
+    }
+
+    #[test]
+    fn csv_export_works() {
+        let report = analysis().report(1);
+
+        let mut matrix = vec![];
+        report.write_matrix_csv(&mut matrix).unwrap();
+        assert_eq!(
+            String::from_utf8(matrix).unwrap(),
+            "reference\\predicted,\"B-a,b\",B-city,I-city,O\n\
+             \"B-a,b\",0,1,0,0\n\
+             B-city,0,1,0,2\n\
+             I-city,0,0,1,0\n\
+             O,0,0,0,3\n"
+        );
+
+        let mut pairs = vec![];
+        report.write_confused_pairs_csv(&mut pairs).unwrap();
+        assert_eq!(
+            String::from_utf8(pairs).unwrap(),
+            "reference,predicted,count\nB-city,O,2\n\"B-a,b\",B-city,1\n"
+        );
+
+        let mut sequences = vec![];
+        report.write_worst_sequences_csv(&mut sequences).unwrap();
+        assert_eq!(
+            String::from_utf8(sequences).unwrap(),
+            "index,probability,num_items,num_errors\n1,0.2,3,2\n"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_export_works() {
+        let report = analysis().report(3);
+
+        let mut json = vec![];
+        report.write_json(&mut json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["labels"], serde_json::json!(report.labels));
+        assert_eq!(
+            value["confused_pairs"][0]["count"],
+            serde_json::json!(report.confused_pairs[0].count)
+        );
+
+        let parsed: super::ErrorReport = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed, report);
+    }
+
+    #[test]
+    fn error_analysis_works_on_tagger_output() {
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let input = vec![
+            vec![
+                "is_first:1",
+                "ngram_1:me",
+                "ngram_1[+1]:two",
+                "shape_ngram_1:xxx",
+            ],
+            vec!["ngram_1:two", "built-in-snips/number:B-", "ngram_1[-1]:me"],
+            vec!["built-in-snips/number:L-", "ngram_1[-1]:two", "is_last:1"],
+        ];
+        let prediction = tagger.tag(&input).unwrap();
+        let probability = tagger.probability(&prediction).unwrap();
+
+        let mut analysis = ErrorAnalysis::new();
+        analysis
+            .accumulate(&["O", "O", "I-snips/number"], &prediction, probability)
+            .unwrap();
+        let report = analysis.report(10);
+        assert_eq!(report.confused_pairs.len(), 1);
+        assert_eq!(report.confused_pairs[0].predicted, "B-snips/number");
+        assert_eq!(report.worst_sequences[0].probability, probability);
+    }
+}
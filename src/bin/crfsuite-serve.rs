@@ -0,0 +1,470 @@
+//! HTTP server tagging JSON requests with one or more models
+//!
+//! ```text
+//! crfsuite-serve [-a ADDRESS] [-w WORKERS] [-b BYTES] [NAME=]MODEL...
+//! ```
+//!
+//! Each worker thread owns a tagger per model. The model of a request is given by its `model`
+//! field, which may be omitted when a single model is served. Bodies larger than the `-b` limit
+//! are rejected with a 413 status.
+//!
+//! - `GET /labels?model=NAME` returns `{"labels": [...]}`
+//! - `POST /tag` with `{"model": NAME, "items": [["attr", ...], {"attr": value, ...}, ...]}`
+//!   returns `{"labels": [...], "probability": p}`
+//! - `POST /marginals` with the same body returns the predicted labels along with
+//!   `"marginals": [{"label": p, ...}, ...]`, the marginal probabilities of every label at each
+//!   position
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::process::exit;
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::{bail, format_err, Result};
+use crfsuite::Tagger;
+use getopts::Options;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("crfsuite-serve: {}", e);
+        exit(1)
+    }
+}
+
+fn run() -> Result<()> {
+    let mut options = Options::new();
+    options
+        .optopt(
+            "a",
+            "address",
+            "listen on ADDRESS (default: 127.0.0.1:8080)",
+            "ADDRESS",
+        )
+        .optopt("w", "workers", "number of worker threads (default: 4)", "N")
+        .optopt(
+            "b",
+            "max-body",
+            "largest accepted request body (default: 1048576)",
+            "BYTES",
+        )
+        .optflag("h", "help", "show the usage and exit");
+    let usage = "USAGE: crfsuite-serve [OPTIONS] [NAME=]MODEL...";
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let matches = options.parse(&args)?;
+    if matches.opt_present("h") || matches.free.is_empty() {
+        print!("{}", options.usage(usage));
+        return Ok(());
+    }
+
+    let models = load_models(&matches.free)?;
+    let address = matches
+        .opt_str("a")
+        .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let workers = match matches.opt_str("w") {
+        Some(n) => n.parse()?,
+        None => 4,
+    };
+    let max_body_size = match matches.opt_str("b") {
+        Some(n) => n.parse()?,
+        None => 1 << 20,
+    };
+
+    let server = Server::http(&address).map_err(|e| format_err!("{}", e))?;
+    println!("listening on {}", address);
+    for handle in serve(Arc::new(server), &models, workers, max_body_size)? {
+        handle
+            .join()
+            .map_err(|_| format_err!("a worker panicked"))?;
+    }
+    Ok(())
+}
+
+/// Reads the models given as `NAME=PATH`, or `PATH` to name them after their file
+fn load_models(args: &[String]) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut models = BTreeMap::new();
+    for arg in args {
+        let (name, path) = match arg.split_once('=') {
+            Some((name, path)) => (name.to_string(), path),
+            None => {
+                let name = Path::new(arg)
+                    .file_stem()
+                    .ok_or_else(|| format_err!("invalid model path {}", arg))?;
+                (name.to_string_lossy().into_owned(), arg.as_str())
+            }
+        };
+        let bytes = fs::read(path).map_err(|e| format_err!("failed to read {}: {}", path, e))?;
+        if models.insert(name.clone(), bytes).is_some() {
+            bail!("model {} given twice", name)
+        }
+    }
+    Ok(models)
+}
+
+/// Starts the worker threads, each with its own taggers
+fn serve(
+    server: Arc<Server>,
+    models: &BTreeMap<String, Vec<u8>>,
+    workers: usize,
+    max_body_size: u64,
+) -> Result<Vec<thread::JoinHandle<()>>> {
+    if workers == 0 {
+        bail!("at least one worker is needed")
+    }
+    (0..workers)
+        .map(|_| {
+            let taggers = Taggers::new(models, max_body_size)?;
+            let server = Arc::clone(&server);
+            Ok(thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    taggers.respond(request);
+                }
+            }))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Item {
+    Names(Vec<String>),
+    Weighted(HashMap<String, f64>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagRequest {
+    model: Option<String>,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Serialize)]
+struct LabelsResponse {
+    labels: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TagResponse {
+    labels: Vec<String>,
+    probability: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct MarginalsResponse {
+    labels: Vec<String>,
+    marginals: Vec<BTreeMap<String, f64>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Error of a request, with its HTTP status code
+#[derive(Debug)]
+struct HttpError(u16, String);
+
+impl HttpError {
+    /// The request could not be parsed or is invalid
+    fn bad_request<E: std::fmt::Display>(e: E) -> HttpError {
+        HttpError(400, e.to_string())
+    }
+
+    /// The server failed to answer a valid request
+    fn internal<E: std::fmt::Display>(e: E) -> HttpError {
+        HttpError(500, e.to_string())
+    }
+}
+
+struct Taggers {
+    taggers: BTreeMap<String, Tagger>,
+    max_body_size: u64,
+}
+
+impl Taggers {
+    fn new(models: &BTreeMap<String, Vec<u8>>, max_body_size: u64) -> Result<Taggers> {
+        let taggers = models
+            .iter()
+            .map(|(name, bytes)| {
+                let tagger = Tagger::create_from_memory(bytes.clone())
+                    .map_err(|e| format_err!("failed to load model {}: {}", name, e))?;
+                Ok((name.clone(), tagger))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Taggers {
+            taggers,
+            max_body_size,
+        })
+    }
+
+    fn respond(&self, mut request: Request) {
+        // one more byte than accepted is read to tell a body at the limit from a larger one
+        let mut body = String::new();
+        let read = request
+            .as_reader()
+            .take(self.max_body_size + 1)
+            .read_to_string(&mut body);
+        let result = match read {
+            Ok(size) if size as u64 > self.max_body_size => Err(HttpError(
+                413,
+                format!("request body larger than {} bytes", self.max_body_size),
+            )),
+            Ok(_) => self.handle(request.method(), request.url(), &body),
+            Err(e) if e.kind() == ErrorKind::InvalidData => Err(HttpError::bad_request(e)),
+            Err(e) => Err(HttpError::internal(e)),
+        };
+        let (status, json) = match result {
+            Ok(json) => (200, json),
+            Err(HttpError(status, error)) => (
+                status,
+                serde_json::to_string(&ErrorResponse { error }).unwrap(),
+            ),
+        };
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(json)
+            .with_status_code(status)
+            .with_header(header);
+        // the client may be gone already, there is no one to report the error to
+        let _ = request.respond(response);
+    }
+
+    fn handle(&self, method: &Method, url: &str, body: &str) -> Result<String, HttpError> {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        match (method, path) {
+            (Method::Get, "/labels") => {
+                let model = query_param(query, "model")?;
+                let tagger = self.tagger(model.as_deref())?;
+                serde_json::to_string(&LabelsResponse {
+                    labels: tagger.labels().map_err(HttpError::internal)?,
+                })
+                .map_err(HttpError::internal)
+            }
+            (Method::Post, "/tag") => {
+                let request: TagRequest =
+                    serde_json::from_str(body).map_err(HttpError::bad_request)?;
+                let tagger = self.tagger(request.model.as_deref())?;
+                let labels = tag(tagger, &request.items).map_err(HttpError::internal)?;
+                let probability = tagger.probability(&labels).map_err(HttpError::internal)?;
+                serde_json::to_string(&TagResponse {
+                    labels,
+                    probability,
+                })
+                .map_err(HttpError::internal)
+            }
+            (Method::Post, "/marginals") => {
+                let request: TagRequest =
+                    serde_json::from_str(body).map_err(HttpError::bad_request)?;
+                let tagger = self.tagger(request.model.as_deref())?;
+                let labels = tag(tagger, &request.items).map_err(HttpError::internal)?;
+                let model_labels = tagger.labels().map_err(HttpError::internal)?;
+                let marginals = (0..labels.len())
+                    .map(|t| {
+                        model_labels
+                            .iter()
+                            .map(|l| Ok((l.clone(), tagger.marginal(l, t)?)))
+                            .collect::<Result<BTreeMap<_, _>>>()
+                    })
+                    .collect::<Result<Vec<_>>>()
+                    .map_err(HttpError::internal)?;
+                serde_json::to_string(&MarginalsResponse { labels, marginals })
+                    .map_err(HttpError::internal)
+            }
+            (_, "/labels") | (_, "/tag") | (_, "/marginals") => {
+                Err(HttpError(405, format!("method {} not allowed", method)))
+            }
+            _ => Err(HttpError(404, format!("unknown endpoint {}", path))),
+        }
+    }
+
+    fn tagger(&self, model: Option<&str>) -> Result<&Tagger, HttpError> {
+        match model {
+            Some(name) => self
+                .taggers
+                .get(name)
+                .ok_or_else(|| HttpError(404, format!("unknown model {}", name))),
+            None if self.taggers.len() == 1 => Ok(self.taggers.values().next().unwrap()),
+            None => Err(HttpError(
+                400,
+                "a model is required when several are served".to_string(),
+            )),
+        }
+    }
+}
+
+/// Percent-decoded value of a parameter of a query string
+fn query_param(query: &str, name: &str) -> Result<Option<String>, HttpError> {
+    for param in query.split('&') {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        if percent_decode(key)? == name {
+            return Ok(Some(percent_decode(value)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Decodes a component of a query string, in which `+` stands for a space
+fn percent_decode(s: &str) -> Result<String, HttpError> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| HttpError(400, format!("invalid escape in {}", s)))?;
+                bytes.push(hex);
+                rest = &rest[2..];
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(HttpError::bad_request)
+}
+
+fn tag(tagger: &Tagger, items: &[Item]) -> Result<Vec<String>> {
+    // items are tagged through a single representation so that both forms can be mixed
+    let items = items
+        .iter()
+        .map(|item| match item {
+            Item::Names(names) => names.iter().map(|n| (n.as_str(), 1.0)).collect(),
+            Item::Weighted(weights) => weights.iter().map(|(n, &v)| (n.as_str(), v)).collect(),
+        })
+        .collect::<Vec<Vec<_>>>();
+    tagger.tag(&items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_models, percent_decode, serve, Taggers};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use tiny_http::{Method, Server};
+
+    fn model_path(name: &str) -> String {
+        format!("{}/test-data/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    const INPUT: &str = r#"[
+        ["is_first:1", "ngram_1:me", "ngram_1[+1]:two", "shape_ngram_1:xxx"],
+        {"ngram_1:two": 1.0, "built-in-snips/number:B-": 1.0, "ngram_1[-1]:me": 1.0},
+        ["built-in-snips/number:L-", "ngram_1[-1]:two", "is_last:1"]
+    ]"#;
+
+    #[test]
+    fn handlers_work() {
+        let models = load_models(&[
+            format!("snips/number={}", model_path("modelo62R_B.crfsuite")),
+            model_path("modela78m0U.crfsuite"),
+        ])
+        .unwrap();
+        assert_eq!(
+            models.keys().collect::<Vec<_>>(),
+            vec!["modela78m0U", "snips/number"]
+        );
+        let taggers = Taggers::new(&models, 1 << 20).unwrap();
+
+        let labels = taggers
+            .handle(&Method::Get, "/labels?lang=en&model=snips%2Fnumber", "")
+            .unwrap();
+        assert!(labels.contains("B-snips/number"));
+
+        let body = format!(r#"{{"model": "snips/number", "items": {}}}"#, INPUT);
+        let tagged = taggers.handle(&Method::Post, "/tag", &body).unwrap();
+        let tagged: serde_json::Value = serde_json::from_str(&tagged).unwrap();
+        assert_eq!(
+            tagged["labels"],
+            serde_json::json!(["O", "B-snips/number", "I-snips/number"])
+        );
+        assert!(tagged["probability"].as_f64().unwrap() > 0.0);
+
+        let marginals = taggers.handle(&Method::Post, "/marginals", &body).unwrap();
+        let marginals: serde_json::Value = serde_json::from_str(&marginals).unwrap();
+        let sum = marginals["marginals"][1]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|p| p.as_f64().unwrap())
+            .sum::<f64>();
+        assert!((sum - 1.0).abs() < 1e-6);
+
+        // several models are served
+        let error = taggers.handle(&Method::Get, "/labels", "").unwrap_err();
+        assert_eq!(error.0, 400);
+        let error = taggers
+            .handle(&Method::Get, "/labels?model=unknown", "")
+            .unwrap_err();
+        assert_eq!(error.0, 404);
+        let error = taggers
+            .handle(&Method::Get, "/labels?model=snips%2", "")
+            .unwrap_err();
+        assert_eq!(error.0, 400);
+        let error = taggers.handle(&Method::Get, "/tag", "").unwrap_err();
+        assert_eq!(error.0, 405);
+        let error = taggers
+            .handle(&Method::Post, "/tag", r#"{"items": 1}"#)
+            .unwrap_err();
+        assert_eq!(error.0, 400);
+
+        // the tagger fails on an attribute it cannot pass to the C code
+        let body = r#"{"model": "snips/number", "items": [["is_first:1\u0000"]]}"#;
+        let error = taggers.handle(&Method::Post, "/tag", body).unwrap_err();
+        assert_eq!(error.0, 500);
+    }
+
+    #[test]
+    fn percent_decoding_works() {
+        assert_eq!(percent_decode("snips%2Fnumber").unwrap(), "snips/number");
+        assert_eq!(percent_decode("a+b%20c").unwrap(), "a b c");
+        assert_eq!(percent_decode("%C3%A9").unwrap(), "é");
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%ff").is_err());
+    }
+
+    #[test]
+    fn server_works() {
+        let models = load_models(&[model_path("modelo62R_B.crfsuite")]).unwrap();
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let address = server.server_addr().to_ip().unwrap();
+        let body = format!(r#"{{"items": {}}}"#, INPUT);
+        let handles = serve(Arc::clone(&server), &models, 2, body.len() as u64).unwrap();
+
+        let post = |body: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(
+                stream,
+                "POST /tag HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+                 Content-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = post(&body);
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(r#""labels":["O","B-snips/number","I-snips/number"]"#));
+
+        // the body is one byte over the limit
+        let response = post(&format!("{} ", body));
+        assert!(response.starts_with("HTTP/1.1 413"));
+
+        server.unblock();
+        server.unblock();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Rust port of the `crfsuite` command line frontend
+//!
+//! ```text
+//! crfsuite learn [-a ALGORITHM] [-p NAME=VALUE]... [-m MODEL] [-g N] [-e M] [-x]
+//!                [--hash-buckets N] [DATA]...
+//! crfsuite tag -m MODEL [-t] [-r] [-p] [-i] [-q] [--hash-buckets N] [DATA]
+//! crfsuite dump MODEL
+//! ```
+//!
+//! Data files are in the CRFsuite data format, the standard input being read when none is given.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::exit;
+use std::time::Instant;
+
+use anyhow::{bail, format_err, Result};
+use crfsuite::text::{Reader, Sequence};
+use crfsuite::{Algorithm, Evaluation, FeatureHashing, Model, Tagger, Trainer};
+use getopts::{Matches, Options};
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(String::as_str) {
+        Some("learn") => learn(&args[1..]),
+        Some("tag") => tag(&args[1..]),
+        Some("dump") => dump(&args[1..]),
+        Some("-h") | Some("--help") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            exit(1)
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("crfsuite: {}", e);
+        exit(1)
+    }
+}
+
+const USAGE: &str = "\
+USAGE: crfsuite <COMMAND> [OPTIONS]
+    COMMAND     Command name to specify the processing
+    OPTIONS     Arguments for the command (optional; command-specific)
+
+COMMAND:
+    learn       Obtain a model from a training set of instances
+    tag         Assign suitable labels to given instances by using a model
+    dump        Output a model in a plain-text format
+
+For the usage of each command, specify -h option in the command argument.
+";
+
+fn parse(args: &[String], options: &Options, usage: &str) -> Result<Option<Matches>> {
+    let matches = options.parse(args)?;
+    if matches.opt_present("h") {
+        print!("{}", options.usage(usage));
+        return Ok(None);
+    }
+    Ok(Some(matches))
+}
+
+fn feature_hashing(matches: &Matches) -> Result<Option<FeatureHashing>> {
+    match matches.opt_str("hash-buckets") {
+        Some(n) => Ok(Some(FeatureHashing::new(n.parse()?)?)),
+        None => Ok(None),
+    }
+}
+
+fn open(path: &str) -> Result<Box<dyn BufRead>> {
+    if path == "-" {
+        return Ok(Box::new(BufReader::new(io::stdin())));
+    }
+    let file = File::open(path).map_err(|e| format_err!("failed to open {}: {}", path, e))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+fn learn(args: &[String]) -> Result<()> {
+    let mut options = Options::new();
+    options
+        .optopt(
+            "t",
+            "type",
+            "graphical model (only crf1d is supported)",
+            "TYPE",
+        )
+        .optopt(
+            "a",
+            "algorithm",
+            "training algorithm: lbfgs (default), l2sgd, ap, pa or arow",
+            "NAME",
+        )
+        .optmulti(
+            "p",
+            "set",
+            "set the training parameter NAME to VALUE",
+            "NAME=VALUE",
+        )
+        .optopt("m", "model", "store the model to FILE", "FILE")
+        .optopt("g", "split", "split the instances into N groups", "N")
+        .optopt(
+            "e",
+            "holdout",
+            "use the M-th group for holdout evaluation",
+            "M",
+        )
+        .optflag(
+            "x",
+            "cross-validate",
+            "repeat holdout evaluations for every group",
+        )
+        .optopt(
+            "",
+            "hash-buckets",
+            "hash the attributes into N buckets",
+            "N",
+        )
+        .optflag(
+            "H",
+            "help-params",
+            "show the parameters of the training algorithm",
+        )
+        .optflag("h", "help", "show the usage of this command and exit");
+    let usage = "USAGE: crfsuite learn [OPTIONS] [DATA1] [DATA2] ...";
+    let matches = match parse(args, &options, usage)? {
+        Some(matches) => matches,
+        None => return Ok(()),
+    };
+
+    if let Some(ty) = matches.opt_str("t") {
+        if ty != "crf1d" {
+            bail!("unknown graphical model {}", ty)
+        }
+    }
+    let algorithm = match matches.opt_str("a") {
+        Some(name) => name.parse()?,
+        None => Algorithm::Lbfgs,
+    };
+    let mut trainer = Trainer::new(algorithm)?;
+
+    if matches.opt_present("H") {
+        println!("PARAMETERS for {} (crf1d):", algorithm.name());
+        println!();
+        for name in trainer.params() {
+            let (ty, help) = trainer.help(&name)?;
+            println!("{} {} = {};", ty, name, trainer.get(&name)?);
+            println!("{}", help);
+            println!();
+        }
+        return Ok(());
+    }
+
+    for param in matches.opt_strs("p") {
+        let (name, value) = param
+            .split_once('=')
+            .ok_or_else(|| format_err!("invalid parameter {}, expected NAME=VALUE", param))?;
+        trainer.set(name, value)?;
+    }
+    trainer.set_feature_hashing(feature_hashing(&matches)?);
+
+    let mut files = matches.free.clone();
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+    println!("Reading the data set(s)");
+    let start = Instant::now();
+    for (group, path) in files.iter().enumerate() {
+        println!("[{}] {}", group + 1, path);
+        for sequence in Reader::new(open(path)?) {
+            let sequence = sequence?;
+            trainer.append(&sequence.items, &sequence.labels, sequence.weight, group)?;
+        }
+    }
+    println!("Number of instances: {}", trainer.num_instances());
+    println!("Seconds required: {:.3}", start.elapsed().as_secs_f64());
+    println!();
+
+    if let Some(split) = matches.opt_str("g") {
+        let split: usize = split.parse()?;
+        if split == 0 {
+            bail!("the number of groups must be positive")
+        }
+        let groups = (0..trainer.num_instances())
+            .map(|i| i % split)
+            .collect::<Vec<_>>();
+        trainer.set_groups(&groups)?;
+    }
+    let num_groups = trainer.num_groups();
+    println!("Number of data sets (groups): {}", num_groups);
+    println!();
+
+    let holdout = match matches.opt_str("e") {
+        Some(m) => {
+            let m: usize = m.parse()?;
+            if m == 0 || m > num_groups {
+                bail!("holdout group {} out of the {} groups", m, num_groups)
+            }
+            Some(m - 1)
+        }
+        None => None,
+    };
+
+    trainer.set_verbose(true);
+    if matches.opt_present("x") {
+        if num_groups < 2 {
+            bail!("cross validation needs at least 2 groups, see the -g option")
+        }
+        for group in 0..num_groups {
+            println!(
+                "===== Cross validation ({}/{}) =====",
+                group + 1,
+                num_groups
+            );
+            trainer.train(None::<&str>, Some(group))?;
+            println!();
+        }
+    } else {
+        trainer.train(matches.opt_str("m"), holdout)?;
+    }
+    Ok(())
+}
+
+fn tag(args: &[String]) -> Result<()> {
+    let mut options = Options::new();
+    options
+        .optopt("m", "model", "read a model from FILE", "FILE")
+        .optflag(
+            "t",
+            "test",
+            "report the performance of the model on the data",
+        )
+        .optflag(
+            "r",
+            "reference",
+            "output the reference labels in the input data",
+        )
+        .optflag(
+            "p",
+            "probability",
+            "output the probability of the label sequences",
+        )
+        .optflag(
+            "i",
+            "marginal",
+            "output the marginal probabilities of the labels",
+        )
+        .optflag("q", "quiet", "suppress the tagging results")
+        .optopt(
+            "",
+            "hash-buckets",
+            "hash the attributes into N buckets, as the model was trained",
+            "N",
+        )
+        .optflag("h", "help", "show the usage of this command and exit");
+    let usage = "USAGE: crfsuite tag [OPTIONS] [DATA]";
+    let matches = match parse(args, &options, usage)? {
+        Some(matches) => matches,
+        None => return Ok(()),
+    };
+
+    let model = matches
+        .opt_str("m")
+        .ok_or_else(|| format_err!("a model is required, see the -m option"))?;
+    let mut tagger = Tagger::create_from_file(&model)?;
+    tagger.set_feature_hashing(feature_hashing(&matches)?);
+    let input = open(matches.free.first().map_or("-", String::as_str))?;
+
+    let mut evaluation = if matches.opt_present("t") {
+        Some(Evaluation::new(&tagger.labels()?)?)
+    } else {
+        None
+    };
+
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    for sequence in Reader::new(input) {
+        let Sequence { labels, items, .. } = sequence?;
+        let predicted = tagger.tag(&items)?;
+
+        if let Some(evaluation) = evaluation.as_mut() {
+            evaluation.accumulate_with_unknown(&labels, &predicted)?;
+        }
+        if matches.opt_present("q") {
+            continue;
+        }
+
+        if matches.opt_present("p") {
+            writeln!(
+                output,
+                "@probability\t{:.6}",
+                tagger.probability(&predicted)?
+            )?;
+        }
+        for (t, label) in predicted.iter().enumerate() {
+            if matches.opt_present("r") {
+                write!(output, "{}\t", labels[t])?;
+            }
+            write!(output, "{}", label)?;
+            if matches.opt_present("i") {
+                write!(output, ":{:.6}", tagger.marginal(label, t)?)?;
+            }
+            writeln!(output)?;
+        }
+        writeln!(output)?;
+    }
+
+    if let Some(mut evaluation) = evaluation {
+        writeln!(output, "{}", evaluation.report())?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn dump(args: &[String]) -> Result<()> {
+    let mut options = Options::new();
+    options.optflag("h", "help", "show the usage of this command and exit");
+    let usage = "USAGE: crfsuite dump [OPTIONS] <MODEL>";
+    let matches = match parse(args, &options, usage)? {
+        Some(matches) => matches,
+        None => return Ok(()),
+    };
+
+    let path = match matches.free.as_slice() {
+        [path] => path,
+        _ => bail!("a single model is required"),
+    };
+    let model = Model::create_from_file(path)?;
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    model.dump(&mut output)?;
+    output.flush()?;
+    Ok(())
+}
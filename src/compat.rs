@@ -0,0 +1,186 @@
+use std::ffi::CString;
+use std::os::raw::c_int;
+
+use anyhow::{bail, Result};
+
+use crate::quantize::quantization;
+use crate::{Model, Quantization};
+
+/// Version of the crf1dm format written by crfsuite 0.12 and python-crfsuite
+pub const FORMAT_VERSION: u32 = 100;
+
+/// Summary of the header of a crf1dm model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub version: u32,
+    /// Quantization of the weights, quantized models can only be loaded by this crate
+    pub quantization: Option<Quantization>,
+    pub num_labels: usize,
+    pub num_attrs: usize,
+    pub num_features: usize,
+}
+
+impl ModelInfo {
+    /// Whether the model can be loaded by the upstream crfsuite and python-crfsuite
+    pub fn is_upstream_compatible(&self) -> bool {
+        self.version == FORMAT_VERSION
+    }
+}
+
+/// Checks a model, as written by crfsuite 0.12, python-crfsuite or this crate, and summarizes
+/// its header
+///
+/// On top of the checks of `Model::create_from_memory`, every label and attribute must be found
+/// back by its name, as the taggers look the inputs up through the hash tables of the model.
+pub fn inspect(bytes: &[u8]) -> Result<ModelInfo> {
+    if bytes.len() < 16 {
+        bail!("model is too small to be a crf1dm model")
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&bytes[12..16]);
+    let version = u32::from_le_bytes(version);
+
+    let quantization = quantization(bytes);
+    if quantization.is_none() && version != FORMAT_VERSION {
+        bail!("unsupported crf1dm version : {}", version)
+    }
+
+    let model = Model::create_from_memory(bytes.to_vec())?;
+    for lid in 0..model.num_labels() as c_int {
+        let label = model.label(lid);
+        if model.label_id(label).ok() != Some(lid) {
+            bail!("label {} is not found back by its name", label)
+        }
+    }
+    for aid in 0..model.num_attrs() as c_int {
+        let attr = model.attr(aid);
+        if model.attr_id(&CString::new(attr)?) != Some(aid) {
+            bail!("attribute {} is not found back by its name", attr)
+        }
+    }
+
+    Ok(ModelInfo {
+        version,
+        quantization,
+        num_labels: model.num_labels(),
+        num_attrs: model.num_attrs(),
+        num_features: model.features().count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+
+    use super::{inspect, FORMAT_VERSION};
+    use crate::tests::file_path;
+    use crate::{EnsembleTagger, Model, ModelData, Quantization, SimpleAttribute, Tagger};
+
+    struct Sequence {
+        input: Vec<Vec<SimpleAttribute>>,
+        labels: Vec<String>,
+        probability: f64,
+    }
+
+    fn fixture(model: &str) -> Vec<Sequence> {
+        let path = file_path(&format!("regression/{}.json", model));
+        let fixture: Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+
+        fixture["sequences"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|sequence| Sequence {
+                input: sequence["items"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|item| {
+                        item.as_object()
+                            .unwrap()
+                            .iter()
+                            .map(|(attr, value)| SimpleAttribute {
+                                attr: attr.clone(),
+                                value: value.as_f64().unwrap(),
+                            })
+                            .collect()
+                    })
+                    .collect(),
+                labels: sequence["labels"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|l| l.as_str().unwrap().to_string())
+                    .collect(),
+                probability: sequence["probability"].as_f64().unwrap(),
+            })
+            .collect()
+    }
+
+    fn check_tagger(tagger: &Tagger, sequences: &[Sequence]) {
+        for sequence in sequences {
+            assert_eq!(tagger.tag(&sequence.input).unwrap(), sequence.labels);
+            let probability = tagger.probability(&sequence.labels).unwrap();
+            assert!((probability - sequence.probability).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn regression_fixtures_match() {
+        for model in &["modela78m0U", "modelo62R_B"] {
+            let path = file_path(&format!("{}.crfsuite", model));
+            let sequences = fixture(model);
+            assert!(!sequences.is_empty());
+
+            check_tagger(&Tagger::create_from_file(&path).unwrap(), &sequences);
+
+            // decoding the weights read from the model gives the same labels
+            let m = Model::create_from_file(&path).unwrap();
+            let ensemble = EnsembleTagger::new(vec![m]).unwrap();
+            for sequence in &sequences {
+                assert_eq!(ensemble.tag(&sequence.input).unwrap(), sequence.labels);
+            }
+
+            // and so does the same model written by this crate
+            let m = Model::create_from_file(&path).unwrap();
+            let bytes = ModelData::from_model(&m).to_bytes().unwrap();
+            check_tagger(&Tagger::create_from_memory(bytes).unwrap(), &sequences);
+        }
+    }
+
+    #[test]
+    fn inspect_works() {
+        let bytes = fs::read(file_path("modelo62R_B.crfsuite")).unwrap();
+        let info = inspect(&bytes).unwrap();
+        let m = Model::create_from_memory(bytes).unwrap();
+
+        assert_eq!(info.version, FORMAT_VERSION);
+        assert!(info.is_upstream_compatible());
+        assert_eq!(info.quantization, None);
+        assert_eq!(info.num_labels, m.num_labels());
+        assert_eq!(info.num_attrs, m.num_attrs());
+        assert_eq!(
+            info.num_features,
+            m.transitions().count() + m.state_features().count()
+        );
+
+        let quantized = inspect(&m.quantize(Quantization::Int8).unwrap()).unwrap();
+        assert_eq!(quantized.quantization, Some(Quantization::Int8));
+        assert!(!quantized.is_upstream_compatible());
+        assert_eq!(quantized.num_features, info.num_features);
+
+        assert!(inspect(b"lCRF").is_err());
+
+        // an attribute renamed without updating the hash table of the model
+        let mut bytes = fs::read(file_path("modelo62R_B.crfsuite")).unwrap();
+        let position = bytes.windows(10).position(|w| w == b"ngram_1:me").unwrap();
+        bytes[position + 9] = b'X';
+        assert!(Model::create_from_memory(bytes.clone()).is_ok());
+        assert_eq!(
+            inspect(&bytes).unwrap_err().to_string(),
+            "attribute ngram_1:mX is not found back by its name"
+        );
+    }
+}
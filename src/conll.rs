@@ -0,0 +1,355 @@
+//! Reader for CoNLL-2000/2003 style column files, and feature templates turning their columns
+//! into attributes, as crfsuite's `chunking.py` and `ner.py` examples do.
+//!
+//! A template such as `w[-1]|w[0]` joins the values of the `w` column of the previous and of the
+//! current token. Attributes are named after their template, the offset being omitted when it is
+//! 0, followed by their value: `w[-1]|w:the|cat`, `ngram_1[+1]:set`.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+use crate::text::Sequence;
+use crate::SimpleAttribute;
+
+/// Iterates over the sentences of a CoNLL file, as the columns of each of their tokens
+pub struct ConllReader<R: BufRead> {
+    reader: R,
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> ConllReader<R> {
+    pub fn new(reader: R) -> ConllReader<R> {
+        ConllReader {
+            reader,
+            line: String::new(),
+            line_number: 0,
+        }
+    }
+
+    fn read_sentence(&mut self) -> Result<Option<Vec<Vec<String>>>> {
+        let mut sentence: Vec<Vec<String>> = vec![];
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                break;
+            }
+            self.line_number += 1;
+
+            let columns = self
+                .line
+                .split_whitespace()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>();
+            if columns.is_empty() {
+                if sentence.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            // document separators of CoNLL-2003
+            if columns[0] == "-DOCSTART-" {
+                continue;
+            }
+            if let Some(first) = sentence.first() {
+                if first.len() != columns.len() {
+                    bail!(
+                        "line {} : expected {} columns, got {}",
+                        self.line_number,
+                        first.len(),
+                        columns.len()
+                    )
+                }
+            }
+            sentence.push(columns);
+        }
+
+        if sentence.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(sentence))
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ConllReader<R> {
+    type Item = Result<Vec<Vec<String>>>;
+
+    fn next(&mut self) -> Option<Result<Vec<Vec<String>>>> {
+        self.read_sentence().transpose()
+    }
+}
+
+/// Feature template combining the values of some fields at offsets relative to the current token
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<(String, isize)>,
+    name: String,
+}
+
+impl Template {
+    pub fn new(parts: &[(&str, isize)]) -> Result<Template> {
+        if parts.is_empty() {
+            bail!("empty feature template")
+        }
+        let name = parts
+            .iter()
+            .map(|&(field, offset)| match offset {
+                0 => field.to_string(),
+                o if o > 0 => format!("{}[+{}]", field, o),
+                o => format!("{}[{}]", field, o),
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        Ok(Template {
+            parts: parts.iter().map(|&(f, o)| (f.to_string(), o)).collect(),
+            name,
+        })
+    }
+
+    /// Name of the template, which prefixes the attributes it produces
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    /// Parses templates such as `w`, `w[-1]|w[0]` or `pos[+1]`
+    fn from_str(template: &str) -> Result<Template> {
+        let mut parts = vec![];
+        for part in template.split('|') {
+            let part = part.trim();
+            let (field, offset) = match part.find('[') {
+                Some(start) if part.ends_with(']') => {
+                    let offset = part[start + 1..part.len() - 1].trim_start_matches('+');
+                    match offset.parse() {
+                        Ok(offset) => (&part[..start], offset),
+                        Err(_) => bail!("invalid offset in feature template : {}", template),
+                    }
+                }
+                Some(_) => bail!("invalid feature template : {}", template),
+                None => (part, 0),
+            };
+            if field.is_empty() {
+                bail!("missing field in feature template : {}", template)
+            }
+            parts.push((field, offset));
+        }
+        Template::new(&parts)
+    }
+}
+
+/// Applies feature templates to the named columns of sentences
+#[derive(Debug, Clone)]
+pub struct FeatureTemplates {
+    fields: HashMap<String, usize>,
+    // templates along with the column of each of their parts
+    templates: Vec<(Template, Vec<(usize, isize)>)>,
+}
+
+impl FeatureTemplates {
+    /// `fields` names the columns of the sentences, in order
+    pub fn new(fields: &[&str], templates: &[Template]) -> Result<FeatureTemplates> {
+        let mut columns = HashMap::new();
+        for (i, field) in fields.iter().enumerate() {
+            if columns.insert(field.to_string(), i).is_some() {
+                bail!("duplicate field {}", field)
+            }
+        }
+
+        let templates = templates
+            .iter()
+            .map(|template| {
+                let columns = template
+                    .parts
+                    .iter()
+                    .map(|(field, offset)| match columns.get(field) {
+                        Some(&column) => Ok((column, *offset)),
+                        None => bail!("unknown field {} in template {}", field, template.name),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok((template.clone(), columns))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(FeatureTemplates {
+            fields: columns,
+            templates,
+        })
+    }
+
+    /// Parses the templates, see `Template::from_str`
+    pub fn parse(fields: &[&str], templates: &[&str]) -> Result<FeatureTemplates> {
+        let templates = templates
+            .iter()
+            .map(|t| t.parse())
+            .collect::<Result<Vec<_>>>()?;
+        FeatureTemplates::new(fields, &templates)
+    }
+
+    /// Attributes of each token of a sentence. A template is skipped when one of its offsets
+    /// falls outside of the sentence.
+    pub fn apply(&self, sentence: &[Vec<String>]) -> Result<Vec<Vec<SimpleAttribute>>> {
+        let num_fields = self.fields.len();
+        if let Some(token) = sentence.iter().find(|t| t.len() < num_fields) {
+            bail!(
+                "expected {} columns, got {} in {:?}",
+                num_fields,
+                token.len(),
+                token
+            )
+        }
+
+        let attributes = (0..sentence.len() as isize)
+            .map(|t| {
+                self.templates
+                    .iter()
+                    .filter_map(|(template, columns)| {
+                        let values = columns
+                            .iter()
+                            .map(|&(column, offset)| {
+                                let position = t + offset;
+                                if position < 0 || position >= sentence.len() as isize {
+                                    None
+                                } else {
+                                    Some(sentence[position as usize][column].as_str())
+                                }
+                            })
+                            .collect::<Option<Vec<_>>>()?;
+                        Some(SimpleAttribute {
+                            attr: format!("{}:{}", template.name, values.join("|")),
+                            value: 1.0,
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(attributes)
+    }
+
+    /// Labeled sequence built from a sentence, the labels being read from the `label` field
+    pub fn sequence(&self, sentence: &[Vec<String>], label: &str) -> Result<Sequence> {
+        let column = match self.fields.get(label) {
+            Some(&column) => column,
+            None => bail!("unknown label field : {}", label),
+        };
+        // applying the templates checks the number of columns of every token
+        let items = self.apply(sentence)?;
+        Ok(Sequence {
+            labels: sentence.iter().map(|t| t[column].clone()).collect(),
+            items,
+            weight: 1.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConllReader, FeatureTemplates, Template};
+    use crate::tests::file_path;
+    use crate::Tagger;
+
+    const DATA: &str = "-DOCSTART- -X- O O\n\
+                        \n\
+                        Confidence NN B-NP\n\
+                        in IN B-PP\n\
+                        the DT B-NP\n\
+                        \n\
+                        \n\
+                        pound NN B-NP\n";
+
+    #[test]
+    fn conll_reader_works() {
+        let sentences = ConllReader::new(DATA.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].len(), 3);
+        assert_eq!(sentences[0][1], vec!["in", "IN", "B-PP"]);
+        assert_eq!(sentences[1], vec![vec!["pound", "NN", "B-NP"]]);
+
+        let invalid = "a DT B-NP\nb NN\n";
+        assert!(ConllReader::new(invalid.as_bytes())
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn template_parsing_works() {
+        let template: Template = "w[-1]|w[0]|pos[+2]".parse().unwrap();
+        assert_eq!(template.name(), "w[-1]|w|pos[+2]");
+        assert_eq!(
+            template,
+            Template::new(&[("w", -1), ("w", 0), ("pos", 2)]).unwrap()
+        );
+
+        assert!("".parse::<Template>().is_err());
+        assert!("w[x]".parse::<Template>().is_err());
+        assert!("w[-1".parse::<Template>().is_err());
+    }
+
+    #[test]
+    fn feature_templates_work() {
+        let sentence = ConllReader::new(DATA.as_bytes()).next().unwrap().unwrap();
+        let templates =
+            FeatureTemplates::parse(&["w", "pos", "y"], &["w", "w[-1]|w[0]", "pos[+1]"]).unwrap();
+
+        let sequence = templates.sequence(&sentence, "y").unwrap();
+
+        assert_eq!(sequence.labels, vec!["B-NP", "B-PP", "B-NP"]);
+        let attrs = sequence
+            .items
+            .iter()
+            .map(|item| item.iter().map(|a| a.attr.as_str()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attrs,
+            vec![
+                vec!["w:Confidence", "pos[+1]:IN"],
+                vec!["w:in", "w[-1]|w:Confidence|in", "pos[+1]:DT"],
+                vec!["w:the", "w[-1]|w:in|the"],
+            ]
+        );
+
+        assert!(FeatureTemplates::parse(&["w"], &["pos"]).is_err());
+        assert!(FeatureTemplates::parse(&["w", "pos", "w"], &["w"]).is_err());
+        assert!(templates.sequence(&sentence, "chunk").is_err());
+
+        let mut short = sentence.clone();
+        short[1].truncate(2);
+        let error = templates.sequence(&short, "y").unwrap_err();
+        assert!(error.to_string().starts_with("expected 3 columns, got 2"));
+    }
+
+    #[test]
+    fn feature_templates_follow_model_naming() {
+        let sentence = vec![
+            vec!["me".to_string()],
+            vec!["two".to_string()],
+            vec!["rare_word".to_string()],
+        ];
+        let templates =
+            FeatureTemplates::parse(&["ngram_1"], &["ngram_1", "ngram_1[-1]", "ngram_1[+1]"])
+                .unwrap();
+        let input = templates.apply(&sentence).unwrap();
+
+        assert_eq!(input[1][0].attr, "ngram_1:two");
+        assert_eq!(input[1][1].attr, "ngram_1[-1]:me");
+        assert_eq!(input[1][2].attr, "ngram_1[+1]:rare_word");
+
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let attributes = tagger.model().unwrap().attributes().collect::<Vec<_>>();
+        for attribute in input.iter().flatten() {
+            assert!(attributes.contains(&attribute.attr.as_str()));
+        }
+        assert_eq!(tagger.tag(&input).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use anyhow::Result;
+
+use crate::{Model, ModelWriter};
+
+/// Plain representation of a crf1dm model, which can be serialized with serde when the `serde`
+/// feature is enabled
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelData {
+    pub labels: Vec<String>,
+    pub attributes: Vec<String>,
+    pub transitions: Vec<Transition>,
+    pub state_features: Vec<StateFeature>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub weight: f64,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateFeature {
+    pub attribute: String,
+    pub label: String,
+    pub weight: f64,
+}
+
+impl ModelData {
+    pub fn from_model(model: &Model) -> ModelData {
+        ModelData {
+            labels: model.labels().map(|l| l.to_string()).collect(),
+            attributes: model.attributes().map(|a| a.to_string()).collect(),
+            transitions: model
+                .transitions()
+                .map(|(from, to, weight)| Transition {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    weight,
+                })
+                .collect(),
+            state_features: model
+                .state_features()
+                .map(|(attribute, label, weight)| StateFeature {
+                    attribute: attribute.to_string(),
+                    label: label.to_string(),
+                    weight,
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the crf1dm bytes of the model, labels and attributes keeping their ids. Features
+    /// referring to labels or attributes missing from the lists are appended to them.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut writer = ModelWriter::new();
+        for label in &self.labels {
+            writer.add_label(label);
+        }
+        for attribute in &self.attributes {
+            writer.add_attribute(attribute);
+        }
+        for t in &self.transitions {
+            writer.set_transition(&t.from, &t.to, t.weight);
+        }
+        for f in &self.state_features {
+            writer.set_state_feature(&f.attribute, &f.label, f.weight);
+        }
+        writer.write_to_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelData;
+    use crate::tests::file_path;
+    use crate::Model;
+
+    #[test]
+    fn model_data_round_trip_works() {
+        let m = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let data = ModelData::from_model(&m);
+
+        assert_eq!(data.labels.len(), m.num_labels());
+        assert_eq!(data.attributes.len(), m.num_attrs());
+
+        let copy = Model::create_from_memory(data.to_bytes().unwrap()).unwrap();
+        assert_eq!(ModelData::from_model(&copy), data);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn model_data_json_round_trip_works() {
+        let m = Model::create_from_file(file_path("modela78m0U.crfsuite")).unwrap();
+        let data = ModelData::from_model(&m);
+
+        let json = serde_json::to_string(&data).unwrap();
+        let parsed: ModelData = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, data);
+
+        let copy = Model::create_from_memory(parsed.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            copy.state_features().collect::<Vec<_>>(),
+            m.state_features().collect::<Vec<_>>()
+        );
+    }
+}
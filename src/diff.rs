@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::Model;
+
+/// Differences between two models, usually two trainings on the same dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelDiff {
+    /// Labels only found in the new model
+    pub added_labels: Vec<String>,
+    /// Labels only found in the old model
+    pub removed_labels: Vec<String>,
+    /// Attributes only found in the new model
+    pub added_attributes: Vec<String>,
+    /// Attributes only found in the old model
+    pub removed_attributes: Vec<String>,
+    /// Transitions whose weight changed beyond the tolerance, largest changes first
+    pub transitions: Vec<WeightChange>,
+    /// State features whose weight changed beyond the tolerance, largest changes first
+    pub state_features: Vec<WeightChange>,
+}
+
+/// Change of the weight of a feature, a missing feature having a weight of 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightChange {
+    /// Label of a transition, or attribute of a state feature
+    pub source: String,
+    pub label: String,
+    pub old_weight: f64,
+    pub new_weight: f64,
+}
+
+impl WeightChange {
+    pub fn delta(&self) -> f64 {
+        self.new_weight - self.old_weight
+    }
+}
+
+pub(crate) fn diff(old: &Model, new: &Model, tolerance: f64) -> ModelDiff {
+    let (added_labels, removed_labels) = added_and_removed(old.labels(), new.labels());
+    let (added_attributes, removed_attributes) =
+        added_and_removed(old.attributes(), new.attributes());
+
+    ModelDiff {
+        added_labels,
+        removed_labels,
+        added_attributes,
+        removed_attributes,
+        transitions: weight_changes(old.transitions(), new.transitions(), tolerance),
+        state_features: weight_changes(old.state_features(), new.state_features(), tolerance),
+    }
+}
+
+fn added_and_removed<'a>(
+    old: impl Iterator<Item = &'a str>,
+    new: impl Iterator<Item = &'a str>,
+) -> (Vec<String>, Vec<String>) {
+    let old = old.collect::<Vec<_>>();
+    let new = new.collect::<Vec<_>>();
+    let old_set = old.iter().collect::<HashSet<_>>();
+    let new_set = new.iter().collect::<HashSet<_>>();
+
+    let added = new
+        .iter()
+        .filter(|v| !old_set.contains(v))
+        .map(|v| v.to_string())
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|v| !new_set.contains(v))
+        .map(|v| v.to_string())
+        .collect();
+    (added, removed)
+}
+
+fn weight_changes<'a>(
+    old: impl Iterator<Item = (&'a str, &'a str, f64)>,
+    new: impl Iterator<Item = (&'a str, &'a str, f64)>,
+    tolerance: f64,
+) -> Vec<WeightChange> {
+    let mut weights: HashMap<(&str, &str), (f64, f64)> = HashMap::new();
+    for (source, label, weight) in old {
+        weights.entry((source, label)).or_insert((0.0, 0.0)).0 = weight;
+    }
+    for (source, label, weight) in new {
+        weights.entry((source, label)).or_insert((0.0, 0.0)).1 = weight;
+    }
+
+    let mut changes = weights
+        .into_iter()
+        .filter(|&(_, (old_weight, new_weight))| (new_weight - old_weight).abs() > tolerance)
+        .map(|((source, label), (old_weight, new_weight))| WeightChange {
+            source: source.to_string(),
+            label: label.to_string(),
+            old_weight,
+            new_weight,
+        })
+        .collect::<Vec<_>>();
+
+    changes.sort_by(|a, b| {
+        b.delta()
+            .abs()
+            .total_cmp(&a.delta().abs())
+            .then_with(|| (&a.source, &a.label).cmp(&(&b.source, &b.label)))
+    });
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WeightChange;
+    use crate::tests::file_path;
+    use crate::{Model, ModelWriter};
+
+    #[test]
+    fn diff_works() {
+        let mut writer = ModelWriter::new();
+        writer.set_transition("O", "B-city", 1.0);
+        writer.set_transition("B-city", "O", 0.5);
+        writer.set_state_feature("capitalized", "B-city", 2.0);
+        writer.set_state_feature("lowercase", "O", 1.0);
+        let old = Model::create_from_memory(writer.write_to_bytes().unwrap()).unwrap();
+
+        let mut writer = ModelWriter::new();
+        writer.set_transition("O", "B-city", 1.001);
+        writer.set_transition("B-city", "O", 1.5);
+        writer.set_transition("O", "B-country", 0.25);
+        writer.set_state_feature("capitalized", "B-city", 1.0);
+        writer.set_state_feature("capitalized", "B-country", 3.0);
+        let new = Model::create_from_memory(writer.write_to_bytes().unwrap()).unwrap();
+
+        let diff = old.diff(&new, 0.01);
+
+        assert_eq!(diff.added_labels, vec!["B-country"]);
+        assert!(diff.removed_labels.is_empty());
+        assert!(diff.added_attributes.is_empty());
+        assert_eq!(diff.removed_attributes, vec!["lowercase"]);
+        assert_eq!(
+            diff.transitions,
+            vec![
+                WeightChange {
+                    source: "B-city".to_string(),
+                    label: "O".to_string(),
+                    old_weight: 0.5,
+                    new_weight: 1.5,
+                },
+                WeightChange {
+                    source: "O".to_string(),
+                    label: "B-country".to_string(),
+                    old_weight: 0.0,
+                    new_weight: 0.25,
+                },
+            ]
+        );
+        assert_eq!(
+            diff.state_features
+                .iter()
+                .map(|c| (c.source.as_str(), c.label.as_str(), c.delta()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("capitalized", "B-country", 3.0),
+                ("capitalized", "B-city", -1.0),
+                ("lowercase", "O", -1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_of_same_model_is_empty() {
+        let m = Model::create_from_file(file_path("modela78m0U.crfsuite")).unwrap();
+        let diff = m.diff(&m, 0.0);
+        assert!(diff.added_labels.is_empty() && diff.removed_labels.is_empty());
+        assert!(diff.added_attributes.is_empty() && diff.removed_attributes.is_empty());
+        assert!(diff.transitions.is_empty() && diff.state_features.is_empty());
+    }
+}
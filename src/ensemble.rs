@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::os::raw::c_int;
+
+use anyhow::{bail, Result};
+use crfsuite_sys::crf1dm_feature_t;
+
+use crate::writer::write_model;
+use crate::{attr_to_c_str, Item, Model};
+
+/// Tagger summing the state and transition scores of several models before decoding, the
+/// models having to share the same labels
+pub struct EnsembleTagger {
+    models: Vec<Model>,
+    labels: Vec<String>,
+    // ensemble label id of each label id of each model
+    label_maps: Vec<Vec<usize>>,
+    // summed transition weights, indexed by `from * num_labels + to`
+    transitions: Vec<f64>,
+}
+
+impl EnsembleTagger {
+    pub fn new(models: Vec<Model>) -> Result<EnsembleTagger> {
+        let refs = models.iter().collect::<Vec<_>>();
+        let labels = shared_labels(&refs)?;
+        let num_labels = labels.len();
+
+        let label_maps = models
+            .iter()
+            .map(|m| {
+                m.labels()
+                    .map(|l| labels.iter().position(|label| label == l).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut transitions = vec![0.0; num_labels * num_labels];
+        for (m, map) in models.iter().zip(&label_maps) {
+            for f in m.features() {
+                if f.type_ == crfsuite_sys::FT_TRANS as c_int {
+                    transitions[map[f.src as usize] * num_labels + map[f.dst as usize]] += f.weight;
+                }
+            }
+        }
+
+        Ok(EnsembleTagger {
+            models,
+            labels,
+            label_maps,
+            transitions,
+        })
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn tag<I: Item>(&self, input: &[I]) -> Result<Vec<String>> {
+        let num_labels = self.labels.len();
+
+        let mut states = vec![vec![0.0; num_labels]; input.len()];
+        let mut buffer = vec![];
+        for (item, scores) in input.iter().zip(states.iter_mut()) {
+            item.for_each_attribute(|attribute| {
+                buffer.clear();
+                attribute.write_attr(&mut buffer);
+                let attr = attr_to_c_str(&mut buffer)?;
+                let value = attribute.get_value();
+                for (m, map) in self.models.iter().zip(&self.label_maps) {
+                    if let Some(aid) = m.attr_id(attr) {
+                        for (lid, weight) in m.attr_weights(aid) {
+                            scores[map[lid as usize]] += value * weight;
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(viterbi(&self.transitions, &states)
+            .into_iter()
+            .map(|lid| self.labels[lid].clone())
+            .collect())
+    }
+}
+
+/// Best label path given the state scores of each item and the transition scores, indexed by
+/// `from * num_labels + to`
+pub(crate) fn viterbi(transitions: &[f64], states: &[Vec<f64>]) -> Vec<usize> {
+    let num_labels = states.first().map_or(0, Vec::len);
+    if num_labels == 0 {
+        return vec![];
+    }
+
+    let mut scores = states[0].clone();
+    let mut backward = vec![vec![0; num_labels]; states.len()];
+    for (t, state) in states.iter().enumerate().skip(1) {
+        let mut next = vec![f64::NEG_INFINITY; num_labels];
+        for (to, score) in next.iter_mut().enumerate() {
+            for (from, previous) in scores.iter().enumerate() {
+                let s = previous + transitions[from * num_labels + to];
+                if s > *score {
+                    *score = s;
+                    backward[t][to] = from;
+                }
+            }
+            *score += state[to];
+        }
+        scores = next;
+    }
+
+    let mut lid = argmax(&scores);
+    let mut path = vec![lid; states.len()];
+    for t in (1..states.len()).rev() {
+        lid = backward[t][lid];
+        path[t - 1] = lid;
+    }
+    path
+}
+
+fn argmax(scores: &[f64]) -> usize {
+    let mut best = 0;
+    for (i, score) in scores.iter().enumerate() {
+        if *score > scores[best] {
+            best = i;
+        }
+    }
+    best
+}
+
+/// Labels of the first model, after checking that all the models have the same ones
+fn shared_labels(models: &[&Model]) -> Result<Vec<String>> {
+    let first = match models.first() {
+        Some(first) => first,
+        None => bail!("at least one model is needed"),
+    };
+    for m in &models[1..] {
+        if m.num_labels() != first.num_labels() || m.labels().any(|l| first.label_id(l).is_err()) {
+            bail!("models do not share the same labels")
+        }
+    }
+    Ok(first.labels().map(|l| l.to_string()).collect())
+}
+
+pub(crate) fn average(models: &[&Model]) -> Result<Vec<u8>> {
+    let labels = shared_labels(models)?;
+    let label_ids = labels
+        .iter()
+        .enumerate()
+        .map(|(lid, l)| (l.as_str(), lid as c_int))
+        .collect::<HashMap<_, _>>();
+
+    let mut attrs = vec![];
+    let mut attr_ids = HashMap::new();
+    let mut features: Vec<crf1dm_feature_t> = vec![];
+    let mut fids: HashMap<_, usize> = HashMap::new();
+
+    for m in models {
+        for mut f in m.features() {
+            if f.type_ == crfsuite_sys::FT_TRANS as c_int {
+                f.src = label_ids[m.label(f.src)];
+            } else {
+                let attr = m.attr(f.src);
+                f.src = *attr_ids.entry(attr).or_insert_with(|| {
+                    attrs.push(attr);
+                    attrs.len() as c_int - 1
+                });
+            }
+            f.dst = label_ids[m.label(f.dst)];
+
+            match fids.get(&(f.type_, f.src, f.dst)) {
+                Some(&fid) => features[fid].weight += f.weight,
+                None => {
+                    fids.insert((f.type_, f.src, f.dst), features.len());
+                    features.push(f);
+                }
+            }
+        }
+    }
+
+    for f in features.iter_mut() {
+        f.weight /= models.len() as f64;
+    }
+
+    let labels = labels.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+    write_model(&labels, &attrs, &features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnsembleTagger;
+    use crate::tests::file_path;
+    use crate::{Model, ModelWriter, Tagger};
+
+    fn input() -> Vec<Vec<(String, String)>> {
+        vec![
+            vec![
+                ("is_first".to_string(), "1".to_string()),
+                ("ngram_1".to_string(), "set".to_string()),
+            ],
+            vec![("ngram_1".to_string(), "the".to_string())],
+            vec![
+                ("ngram_1".to_string(), "temperature".to_string()),
+                ("is_last".to_string(), "1".to_string()),
+            ],
+        ]
+    }
+
+    #[test]
+    fn average_works() {
+        let mut writer = ModelWriter::new();
+        writer.set_transition("O", "B-city", 1.0);
+        writer.set_state_feature("capitalized", "B-city", 2.0);
+        let a = Model::create_from_memory(writer.write_to_bytes().unwrap()).unwrap();
+
+        let mut writer = ModelWriter::new();
+        writer.set_transition("O", "B-city", 3.0);
+        writer.set_transition("B-city", "O", -1.0);
+        writer.set_state_feature("in_gazetteer", "B-city", 4.0);
+        let b = Model::create_from_memory(writer.write_to_bytes().unwrap()).unwrap();
+
+        let averaged = Model::create_from_memory(Model::average(&[&a, &b]).unwrap()).unwrap();
+
+        assert_eq!(averaged.labels().collect::<Vec<_>>(), vec!["O", "B-city"]);
+        assert_eq!(
+            averaged.transitions().collect::<Vec<_>>(),
+            vec![("O", "B-city", 2.0), ("B-city", "O", -0.5)]
+        );
+        assert_eq!(
+            averaged.state_features().collect::<Vec<_>>(),
+            vec![
+                ("capitalized", "B-city", 1.0),
+                ("in_gazetteer", "B-city", 2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn models_with_different_labels_are_rejected() {
+        let a = Model::create_from_file(file_path("modela78m0U.crfsuite")).unwrap();
+        let b = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        assert!(Model::average(&[]).is_err());
+        assert!(Model::average(&[&a, &b]).is_err());
+        assert!(EnsembleTagger::new(vec![a, b]).is_err());
+    }
+
+    #[test]
+    fn ensemble_tagger_works() {
+        let path = file_path("modela78m0U.crfsuite");
+        let expected = Tagger::create_from_file(&path)
+            .unwrap()
+            .tag(&input())
+            .unwrap();
+
+        let single = EnsembleTagger::new(vec![Model::create_from_file(&path).unwrap()]).unwrap();
+        assert_eq!(single.tag(&input()).unwrap(), expected);
+
+        let twice = EnsembleTagger::new(vec![
+            Model::create_from_file(&path).unwrap(),
+            Model::create_from_file(&path).unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(twice.tag(&input()).unwrap(), expected);
+        assert!(twice.tag::<Vec<(String, String)>>(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,671 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::mem::zeroed;
+use std::os::raw::c_int;
+
+use anyhow::{bail, Result};
+
+use crate::scheme::{spans, Span};
+
+/// Accumulates reference and predicted label sequences to compute the performance of a model,
+/// as the `crfsuite` command does
+pub struct Evaluation {
+    eval: crfsuite_sys::crfsuite_evaluation_t,
+    labels: Vec<String>,
+    label_ids: HashMap<String, c_int>,
+}
+
+// the evaluation owns its table of label evaluations
+unsafe impl Send for Evaluation {}
+
+/// Performance of a model on one label
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelEvaluation {
+    pub label: String,
+    /// Number of correct predictions
+    pub num_correct: usize,
+    /// Number of occurrences of the label in the reference
+    pub num_observation: usize,
+    /// Number of predictions of the label
+    pub num_model: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvaluationReport {
+    /// Evaluations of the labels found in the reference
+    pub labels: Vec<LabelEvaluation>,
+    pub num_items: usize,
+    pub num_items_correct: usize,
+    pub item_accuracy: f64,
+    pub num_instances: usize,
+    pub num_instances_correct: usize,
+    pub instance_accuracy: f64,
+    /// Averages over all the labels, the ones missing from the reference counting as 0
+    pub macro_precision: f64,
+    pub macro_recall: f64,
+    pub macro_f1: f64,
+}
+
+impl Evaluation {
+    pub fn new<S: AsRef<str>>(labels: &[S]) -> Result<Evaluation> {
+        if labels.is_empty() {
+            // the macro averages would divide by zero
+            bail!("no labels to evaluate")
+        }
+        let labels = labels
+            .iter()
+            .map(|l| l.as_ref().to_string())
+            .collect::<Vec<_>>();
+        let label_ids = labels
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (l.clone(), i as c_int))
+            .collect::<HashMap<_, _>>();
+        if label_ids.len() != labels.len() {
+            bail!("duplicated labels in evaluation")
+        }
+
+        let mut eval: crfsuite_sys::crfsuite_evaluation_t = unsafe { zeroed() };
+        unsafe { crfsuite_sys::crfsuite_evaluation_init(&mut eval, labels.len() as c_int) };
+        if eval.tbl.is_null() {
+            bail!("error while creating evaluation : null pointer returned by C code...")
+        }
+
+        Ok(Evaluation {
+            eval,
+            labels,
+            label_ids,
+        })
+    }
+
+    /// Adds the labels of an instance, predicted labels being compared to the reference ones
+    pub fn accumulate<S: AsRef<str>, T: AsRef<str>>(
+        &mut self,
+        reference: &[S],
+        prediction: &[T],
+    ) -> Result<()> {
+        if reference.len() != prediction.len() {
+            bail!(
+                "reference has {} labels but prediction has {}",
+                reference.len(),
+                prediction.len()
+            )
+        }
+        let reference = self.label_ids(reference)?;
+        let prediction = self.label_ids(prediction)?;
+
+        let r = unsafe {
+            crfsuite_sys::crfsuite_evaluation_accmulate(
+                &mut self.eval,
+                reference.as_ptr(),
+                prediction.as_ptr(),
+                reference.len() as c_int,
+            )
+        };
+        if r != 0 {
+            bail!("error while accumulating evaluation : non zero C return code...")
+        }
+        Ok(())
+    }
+
+    /// Adds the labels of an instance as `accumulate` does, reference labels unknown to the
+    /// evaluation being counted apart as the `crfsuite tag` command does: they count as errors
+    /// in the accuracies, but are left out of the labels of the report and of the macro averages
+    pub fn accumulate_with_unknown<S: AsRef<str>, T: AsRef<str>>(
+        &mut self,
+        reference: &[S],
+        prediction: &[T],
+    ) -> Result<()> {
+        if reference.len() != prediction.len() {
+            bail!(
+                "reference has {} labels but prediction has {}",
+                reference.len(),
+                prediction.len()
+            )
+        }
+        let unknown = self.labels.len() as c_int;
+        let reference = reference
+            .iter()
+            .map(|l| self.label_ids.get(l.as_ref()).copied().unwrap_or(unknown))
+            .collect::<Vec<_>>();
+        let prediction = self.label_ids(prediction)?;
+
+        // the C code allocates the slot of the unknown labels, but refuses to accumulate in it
+        let table = unsafe { std::slice::from_raw_parts_mut(self.eval.tbl, self.labels.len() + 1) };
+        let mut num_correct = 0;
+        for (&lr, &lt) in reference.iter().zip(&prediction) {
+            table[lr as usize].num_observation += 1;
+            table[lt as usize].num_model += 1;
+            if lr == lt {
+                table[lr as usize].num_correct += 1;
+                num_correct += 1;
+            }
+        }
+        self.eval.item_total_num += reference.len() as c_int;
+        if num_correct == reference.len() {
+            self.eval.inst_total_correct += 1;
+        }
+        self.eval.inst_total_num += 1;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        unsafe { crfsuite_sys::crfsuite_evaluation_clear(&mut self.eval) }
+    }
+
+    pub fn report(&mut self) -> EvaluationReport {
+        // finalizing adds to the totals and divides the macro averages, which are reset so that
+        // reports can be made while accumulating
+        self.eval.item_total_correct = 0;
+        self.eval.item_total_model = 0;
+        self.eval.item_total_observation = 0;
+        self.eval.macro_precision = 0.0;
+        self.eval.macro_recall = 0.0;
+        self.eval.macro_fmeasure = 0.0;
+        unsafe { crfsuite_sys::crfsuite_evaluation_finalize(&mut self.eval) };
+
+        let table = unsafe { std::slice::from_raw_parts(self.eval.tbl, self.labels.len()) };
+        let labels = self
+            .labels
+            .iter()
+            .zip(table)
+            .filter(|(_, e)| e.num_observation > 0)
+            .map(|(label, e)| LabelEvaluation {
+                label: label.clone(),
+                num_correct: e.num_correct as usize,
+                num_observation: e.num_observation as usize,
+                num_model: e.num_model as usize,
+                precision: e.precision,
+                recall: e.recall,
+                f1: e.fmeasure,
+            })
+            .collect();
+
+        EvaluationReport {
+            labels,
+            num_items: self.eval.item_total_num as usize,
+            num_items_correct: self.eval.item_total_correct as usize,
+            item_accuracy: self.eval.item_accuracy,
+            num_instances: self.eval.inst_total_num as usize,
+            num_instances_correct: self.eval.inst_total_correct as usize,
+            instance_accuracy: self.eval.inst_accuracy,
+            macro_precision: self.eval.macro_precision,
+            macro_recall: self.eval.macro_recall,
+            macro_f1: self.eval.macro_fmeasure,
+        }
+    }
+
+    fn label_ids<S: AsRef<str>>(&self, labels: &[S]) -> Result<Vec<c_int>> {
+        labels
+            .iter()
+            .map(|l| match self.label_ids.get(l.as_ref()) {
+                Some(&lid) => Ok(lid),
+                None => bail!("unknown label : {}", l.as_ref()),
+            })
+            .collect()
+    }
+}
+
+impl Drop for Evaluation {
+    fn drop(&mut self) {
+        unsafe { crfsuite_sys::crfsuite_evaluation_finish(&mut self.eval) }
+    }
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .labels
+            .iter()
+            .map(|l| l.label.chars().count())
+            .chain(Some("macro average".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$} {:>7} {:>7} {:>7} {:>9} {:>9} {:>9}",
+            "label",
+            "match",
+            "model",
+            "ref",
+            "precision",
+            "recall",
+            "f1",
+            width = width
+        )?;
+        for l in &self.labels {
+            writeln!(
+                f,
+                "{:<width$} {:>7} {:>7} {:>7} {:>9.4} {:>9.4} {:>9.4}",
+                l.label,
+                l.num_correct,
+                l.num_model,
+                l.num_observation,
+                l.precision,
+                l.recall,
+                l.f1,
+                width = width
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<width$} {:>7} {:>7} {:>7} {:>9.4} {:>9.4} {:>9.4}",
+            "macro average",
+            "",
+            "",
+            "",
+            self.macro_precision,
+            self.macro_recall,
+            self.macro_f1,
+            width = width
+        )?;
+        writeln!(
+            f,
+            "item accuracy: {} / {} ({:.4})",
+            self.num_items_correct, self.num_items, self.item_accuracy
+        )?;
+        write!(
+            f,
+            "instance accuracy: {} / {} ({:.4})",
+            self.num_instances_correct, self.num_instances, self.instance_accuracy
+        )
+    }
+}
+
+/// How predicted entities are matched with the reference ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanMatching {
+    /// Entities match when they have the same type and the same boundaries
+    Strict,
+    /// Entities match when they have the same type and overlap
+    Lenient,
+}
+
+/// Entity level evaluation of label sequences tagged with BIO, BIOES or BILOU, as seqeval does
+#[derive(Debug, Clone)]
+pub struct SpanEvaluation {
+    matching: SpanMatching,
+    counts: BTreeMap<String, SpanCounts>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SpanCounts {
+    num_predicted: usize,
+    num_reference: usize,
+    // predicted entities matching a reference one, and reference entities being matched
+    num_predicted_correct: usize,
+    num_reference_correct: usize,
+}
+
+/// Performance of a model on one type of entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityEvaluation {
+    pub entity: String,
+    pub num_predicted: usize,
+    pub num_reference: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanReport {
+    /// Evaluations of the entities, sorted by type
+    pub entities: Vec<EntityEvaluation>,
+    /// Scores over all the entities
+    pub micro_precision: f64,
+    pub micro_recall: f64,
+    pub micro_f1: f64,
+    /// Averages of the scores of the types of entities
+    pub macro_precision: f64,
+    pub macro_recall: f64,
+    pub macro_f1: f64,
+}
+
+impl SpanEvaluation {
+    pub fn new(matching: SpanMatching) -> SpanEvaluation {
+        SpanEvaluation {
+            matching,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the labels of an instance, predicted entities being compared to the reference ones
+    pub fn accumulate<S: AsRef<str>, T: AsRef<str>>(
+        &mut self,
+        reference: &[S],
+        prediction: &[T],
+    ) -> Result<()> {
+        if reference.len() != prediction.len() {
+            bail!(
+                "reference has {} labels but prediction has {}",
+                reference.len(),
+                prediction.len()
+            )
+        }
+        let reference = spans(reference);
+        let prediction = spans(prediction);
+
+        let matches = |a: &Span, b: &Span| {
+            a.entity == b.entity
+                && match self.matching {
+                    SpanMatching::Strict => a.start == b.start && a.end == b.end,
+                    SpanMatching::Lenient => a.overlaps(b),
+                }
+        };
+        let predicted_correct = prediction
+            .iter()
+            .map(|p| reference.iter().any(|r| matches(p, r)))
+            .collect::<Vec<_>>();
+        let reference_correct = reference
+            .iter()
+            .map(|r| prediction.iter().any(|p| matches(p, r)))
+            .collect::<Vec<_>>();
+
+        for (span, correct) in prediction.iter().zip(predicted_correct) {
+            let counts = self.counts.entry(span.entity.clone()).or_default();
+            counts.num_predicted += 1;
+            counts.num_predicted_correct += correct as usize;
+        }
+        for (span, correct) in reference.iter().zip(reference_correct) {
+            let counts = self.counts.entry(span.entity.clone()).or_default();
+            counts.num_reference += 1;
+            counts.num_reference_correct += correct as usize;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    pub fn report(&self) -> SpanReport {
+        let entities = self
+            .counts
+            .iter()
+            .map(|(entity, c)| {
+                let (precision, recall, f1) = scores(
+                    c.num_predicted_correct,
+                    c.num_predicted,
+                    c.num_reference_correct,
+                    c.num_reference,
+                );
+                EntityEvaluation {
+                    entity: entity.clone(),
+                    num_predicted: c.num_predicted,
+                    num_reference: c.num_reference,
+                    precision,
+                    recall,
+                    f1,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let total = self
+            .counts
+            .values()
+            .fold(SpanCounts::default(), |mut total, c| {
+                total.num_predicted += c.num_predicted;
+                total.num_reference += c.num_reference;
+                total.num_predicted_correct += c.num_predicted_correct;
+                total.num_reference_correct += c.num_reference_correct;
+                total
+            });
+        let (micro_precision, micro_recall, micro_f1) = scores(
+            total.num_predicted_correct,
+            total.num_predicted,
+            total.num_reference_correct,
+            total.num_reference,
+        );
+
+        let average = |score: fn(&EntityEvaluation) -> f64| {
+            if entities.is_empty() {
+                0.0
+            } else {
+                entities.iter().map(score).sum::<f64>() / entities.len() as f64
+            }
+        };
+
+        SpanReport {
+            macro_precision: average(|e| e.precision),
+            macro_recall: average(|e| e.recall),
+            macro_f1: average(|e| e.f1),
+            entities,
+            micro_precision,
+            micro_recall,
+            micro_f1,
+        }
+    }
+}
+
+/// Precision, recall and F1, which are 0 when undefined
+fn scores(
+    num_predicted_correct: usize,
+    num_predicted: usize,
+    num_reference_correct: usize,
+    num_reference: usize,
+) -> (f64, f64, f64) {
+    let ratio = |a: usize, b: usize| if b == 0 { 0.0 } else { a as f64 / b as f64 };
+    let precision = ratio(num_predicted_correct, num_predicted);
+    let recall = ratio(num_reference_correct, num_reference);
+    let f1 = if precision + recall > 0.0 {
+        2.0 * precision * recall / (precision + recall)
+    } else {
+        0.0
+    };
+    (precision, recall, f1)
+}
+
+impl fmt::Display for SpanReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .entities
+            .iter()
+            .map(|e| e.entity.chars().count())
+            .chain(Some("macro average".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "entity",
+            "predicted",
+            "ref",
+            "precision",
+            "recall",
+            "f1",
+            width = width
+        )?;
+        for e in &self.entities {
+            writeln!(
+                f,
+                "{:<width$} {:>9} {:>9} {:>9.4} {:>9.4} {:>9.4}",
+                e.entity,
+                e.num_predicted,
+                e.num_reference,
+                e.precision,
+                e.recall,
+                e.f1,
+                width = width
+            )?;
+        }
+        for &(name, precision, recall, f1) in &[
+            (
+                "micro average",
+                self.micro_precision,
+                self.micro_recall,
+                self.micro_f1,
+            ),
+            (
+                "macro average",
+                self.macro_precision,
+                self.macro_recall,
+                self.macro_f1,
+            ),
+        ] {
+            writeln!(
+                f,
+                "{:<width$} {:>9} {:>9} {:>9.4} {:>9.4} {:>9.4}",
+                name,
+                "",
+                "",
+                precision,
+                recall,
+                f1,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluation, LabelEvaluation, SpanEvaluation, SpanMatching};
+    use crate::tests::file_path;
+    use crate::Tagger;
+
+    #[test]
+    fn evaluation_works() {
+        let mut evaluation = Evaluation::new(&["O", "B-city", "I-city", "B-country"]).unwrap();
+
+        evaluation
+            .accumulate(&["O", "B-city", "I-city"], &["O", "B-city", "I-city"])
+            .unwrap();
+        evaluation
+            .accumulate(&["B-city", "O"], &["O", "O"])
+            .unwrap();
+
+        assert!(evaluation.accumulate(&["O"], &["O", "O"]).is_err());
+        assert!(evaluation.accumulate(&["O"], &["B-unknown"]).is_err());
+
+        let report = evaluation.report();
+        assert_eq!(report.num_items, 5);
+        assert_eq!(report.num_items_correct, 4);
+        assert_eq!(report.item_accuracy, 0.8);
+        assert_eq!(report.num_instances, 2);
+        assert_eq!(report.num_instances_correct, 1);
+        assert_eq!(report.instance_accuracy, 0.5);
+
+        // B-country is not in the reference
+        assert_eq!(report.labels.len(), 3);
+        assert_eq!(
+            report.labels[1],
+            LabelEvaluation {
+                label: "B-city".to_string(),
+                num_correct: 1,
+                num_observation: 2,
+                num_model: 1,
+                precision: 1.0,
+                recall: 0.5,
+                f1: 2.0 / 3.0,
+            }
+        );
+        let o = &report.labels[0];
+        assert_eq!((o.precision, o.recall), (2.0 / 3.0, 1.0));
+        assert!((report.macro_precision - (2.0 / 3.0 + 1.0 + 1.0) / 4.0).abs() < 1e-9);
+
+        // reports can be made again
+        assert_eq!(evaluation.report(), report);
+
+        let table = report.to_string();
+        assert!(table.starts_with("label"));
+        assert!(table.contains("item accuracy: 4 / 5 (0.8000)"));
+
+        evaluation.clear();
+        assert_eq!(evaluation.report().num_items, 0);
+    }
+
+    #[test]
+    fn invalid_labels_are_rejected() {
+        assert!(Evaluation::new::<&str>(&[]).is_err());
+        assert!(Evaluation::new(&["O", "B-city", "O"]).is_err());
+    }
+
+    #[test]
+    fn unknown_references_are_counted_apart() {
+        let mut evaluation = Evaluation::new(&["O", "B-city"]).unwrap();
+        evaluation
+            .accumulate_with_unknown(&["O", "B-city"], &["O", "B-city"])
+            .unwrap();
+        evaluation
+            .accumulate_with_unknown(&["O", "B-unknown"], &["O", "O"])
+            .unwrap();
+        assert!(evaluation
+            .accumulate_with_unknown(&["O"], &["B-unknown"])
+            .is_err());
+
+        let report = evaluation.report();
+        assert_eq!((report.num_items, report.num_items_correct), (4, 3));
+        assert_eq!((report.num_instances, report.num_instances_correct), (2, 1));
+        let labels = report.labels.iter().map(|l| &l.label).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["O", "B-city"]);
+        assert_eq!(report.labels[0].num_model, 3);
+        assert!((report.macro_precision - (2.0 / 3.0 + 1.0) / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn span_evaluation_works() {
+        let reference = vec!["B-city", "I-city", "O", "B-country", "O", "U-number"];
+        let prediction = vec!["B-city", "O", "O", "B-country", "O", "U-number"];
+
+        let mut strict = SpanEvaluation::new(SpanMatching::Strict);
+        strict.accumulate(&reference, &prediction).unwrap();
+        strict
+            .accumulate(&["B-country", "L-country"], &["O", "U-city"])
+            .unwrap();
+        assert!(strict.accumulate(&["O"], &["O", "O"]).is_err());
+
+        let report = strict.report();
+        let entities = report
+            .entities
+            .iter()
+            .map(|e| (e.entity.as_str(), e.num_predicted, e.num_reference, e.f1))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entities,
+            vec![
+                ("city", 2, 1, 0.0),
+                ("country", 1, 2, 2.0 / 3.0),
+                ("number", 1, 1, 1.0)
+            ]
+        );
+        assert_eq!(report.micro_precision, 0.5);
+        assert_eq!(report.micro_recall, 0.5);
+        assert!((report.macro_f1 - (2.0 / 3.0 + 1.0) / 3.0).abs() < 1e-9);
+
+        let mut lenient = SpanEvaluation::new(SpanMatching::Lenient);
+        lenient.accumulate(&reference, &prediction).unwrap();
+        let report = lenient.report();
+        assert_eq!(report.micro_precision, 1.0);
+        assert_eq!(report.micro_recall, 1.0);
+        assert!(report.to_string().contains("micro average"));
+    }
+
+    #[test]
+    fn span_evaluation_works_on_tagger_output() {
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let input = vec![
+            vec![
+                "is_first:1",
+                "ngram_1:me",
+                "ngram_1[+1]:two",
+                "shape_ngram_1:xxx",
+            ],
+            vec!["ngram_1:two", "built-in-snips/number:B-", "ngram_1[-1]:me"],
+            vec!["built-in-snips/number:L-", "ngram_1[-1]:two", "is_last:1"],
+        ];
+        let prediction = tagger.tag(&input).unwrap();
+
+        let mut evaluation = SpanEvaluation::new(SpanMatching::Strict);
+        evaluation
+            .accumulate(&["O", "B-snips/number", "I-snips/number"], &prediction)
+            .unwrap();
+        assert_eq!(evaluation.report().micro_f1, 1.0);
+    }
+}
@@ -0,0 +1,418 @@
+//! Feature extraction producing the attributes used by our models, such as
+//! `shape_ngram_2[-1]:Xxx xxx`, `is_first[-2]:1` or `word_cluster_brown_clusters[+1]:0110`.
+//!
+//! Each feature is computed on the token at some offset of the current one, the offset being
+//! appended to the feature name unless it is 0.
+
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+
+use anyhow::{bail, Result};
+
+use crate::{Gazetteer, SimpleAttribute};
+
+/// Replaces the words missing from the common words of an ngram feature
+pub const RARE_WORD: &str = "rare_word";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feature {
+    /// Lowercased tokens starting at the token, the ones missing from `common_words`, if any,
+    /// being replaced by `rare_word`
+    Ngram {
+        n: usize,
+        common_words: Option<HashSet<String>>,
+    },
+    /// Shapes of the tokens starting at the token: `xxx`, `Xxx`, `XXX` or `xX`
+    ShapeNgram {
+        n: usize,
+    },
+    IsDigit,
+    IsFirst,
+    IsLast,
+    /// First characters of the lowercased token
+    Prefix {
+        n: usize,
+    },
+    /// Last characters of the lowercased token
+    Suffix {
+        n: usize,
+    },
+    /// Cluster of the lowercased token, when it has one
+    WordCluster {
+        name: String,
+        clusters: HashMap<String, String>,
+    },
+    /// Tag of the token when it is part of an entry of the gazetteer
+    Gazetteer(Gazetteer),
+}
+
+impl Feature {
+    pub fn name(&self) -> String {
+        match self {
+            Feature::Ngram { n, .. } => format!("ngram_{}", n),
+            Feature::ShapeNgram { n } => format!("shape_ngram_{}", n),
+            Feature::IsDigit => "is_digit".to_string(),
+            Feature::IsFirst => "is_first".to_string(),
+            Feature::IsLast => "is_last".to_string(),
+            Feature::Prefix { n } => format!("prefix_{}", n),
+            Feature::Suffix { n } => format!("suffix_{}", n),
+            Feature::WordCluster { name, .. } => format!("word_cluster_{}", name),
+            Feature::Gazetteer(gazetteer) => gazetteer.name().to_string(),
+        }
+    }
+
+    /// Value of the feature for each token
+    fn values(&self, tokens: &[String]) -> Vec<Option<String>> {
+        // gazetteer matches depend on the whole sentence, annotate it once
+        let annotations = match self {
+            Feature::Gazetteer(gazetteer) => gazetteer.annotate(tokens),
+            _ => vec![],
+        };
+        (0..tokens.len())
+            .map(|p| self.compute(tokens, p, &annotations))
+            .collect()
+    }
+
+    /// Value of the feature for the token at `position`, if any. `annotations` are the gazetteer
+    /// tags of the sentence, for gazetteer features.
+    fn compute(
+        &self,
+        tokens: &[String],
+        position: usize,
+        annotations: &[Option<String>],
+    ) -> Option<String> {
+        let token = &tokens[position];
+        match self {
+            Feature::Ngram { n, common_words } => {
+                let words = ngram(tokens, position, *n)?.map(|t| {
+                    let word = t.to_lowercase();
+                    match common_words {
+                        Some(common_words) if !common_words.contains(&word) => {
+                            RARE_WORD.to_string()
+                        }
+                        _ => word,
+                    }
+                });
+                Some(words.collect::<Vec<_>>().join(" "))
+            }
+            Feature::ShapeNgram { n } => Some(
+                ngram(tokens, position, *n)?
+                    .map(|t| shape(t))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Feature::IsDigit => {
+                if !token.is_empty() && token.chars().all(|c| c.is_numeric()) {
+                    Some("1".to_string())
+                } else {
+                    None
+                }
+            }
+            Feature::IsFirst => {
+                if position == 0 {
+                    Some("1".to_string())
+                } else {
+                    None
+                }
+            }
+            Feature::IsLast => {
+                if position + 1 == tokens.len() {
+                    Some("1".to_string())
+                } else {
+                    None
+                }
+            }
+            Feature::Prefix { n } => Some(token.to_lowercase().chars().take(*n).collect()),
+            Feature::Suffix { n } => {
+                let token = token.to_lowercase();
+                let skip = token.chars().count().saturating_sub(*n);
+                Some(token.chars().skip(skip).collect())
+            }
+            Feature::WordCluster { clusters, .. } => clusters.get(&token.to_lowercase()).cloned(),
+            Feature::Gazetteer(_) => annotations.get(position).cloned().flatten(),
+        }
+    }
+}
+
+fn ngram(
+    tokens: &[String],
+    position: usize,
+    n: usize,
+) -> Option<impl Iterator<Item = &String> + '_> {
+    if n == 0 || position + n > tokens.len() {
+        None
+    } else {
+        Some(tokens[position..position + n].iter())
+    }
+}
+
+fn shape(token: &str) -> &'static str {
+    let has_lower = token.chars().any(|c| c.is_lowercase());
+    let has_upper = token.chars().any(|c| c.is_uppercase());
+    let first_upper = matches!(token.chars().next(), Some(c) if c.is_uppercase());
+    if has_lower && !has_upper {
+        "xxx"
+    } else if has_upper && !has_lower {
+        "XXX"
+    } else if first_upper && token.chars().skip(1).all(|c| !c.is_uppercase()) {
+        "Xxx"
+    } else {
+        "xX"
+    }
+}
+
+/// Extracts the attributes of each token of a sentence, from features computed in windows
+/// around the tokens
+#[derive(Debug, Clone, Default)]
+pub struct FeatureExtractor {
+    features: Vec<(Feature, Vec<isize>)>,
+}
+
+impl FeatureExtractor {
+    pub fn new() -> FeatureExtractor {
+        FeatureExtractor::default()
+    }
+
+    /// Adds a feature computed on the tokens at each of the `offsets` of the current token
+    pub fn add(&mut self, feature: Feature, offsets: &[isize]) {
+        self.features.push((feature, offsets.to_vec()));
+    }
+
+    pub fn extract<S: AsRef<str>>(&self, tokens: &[S]) -> Vec<Vec<SimpleAttribute>> {
+        let tokens = tokens
+            .iter()
+            .map(|t| t.as_ref().to_string())
+            .collect::<Vec<_>>();
+
+        let values = self
+            .features
+            .iter()
+            .map(|(feature, _)| feature.values(&tokens))
+            .collect::<Vec<_>>();
+
+        (0..tokens.len() as isize)
+            .map(|t| {
+                let mut attributes = vec![];
+                for ((feature, offsets), values) in self.features.iter().zip(&values) {
+                    let name = feature.name();
+                    for &offset in offsets {
+                        let position = t + offset;
+                        if position < 0 || position >= tokens.len() as isize {
+                            continue;
+                        }
+                        if let Some(value) = &values[position as usize] {
+                            attributes.push(SimpleAttribute {
+                                attr: format!("{}{}:{}", name, offset_suffix(offset), value),
+                                value: 1.0,
+                            });
+                        }
+                    }
+                }
+                attributes
+            })
+            .collect()
+    }
+}
+
+/// Reads the clusters of words from the output of Brown clustering, made of `bitstring<TAB>word`
+/// lines optionally followed by the count of the word. Bitstrings are truncated to `prefix_len`
+/// bits when it is given, to get coarser clusters.
+pub fn load_brown_clusters<R: BufRead>(
+    reader: R,
+    prefix_len: Option<usize>,
+) -> Result<HashMap<String, String>> {
+    let mut clusters = HashMap::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let (bits, word) = match (fields.next(), fields.next()) {
+            (Some(bits), Some(word)) if !bits.is_empty() && !word.is_empty() => (bits, word),
+            _ => bail!("line {} : expected a bitstring and a word", i + 1),
+        };
+        if !bits.chars().all(|c| c == '0' || c == '1') {
+            bail!("line {} : invalid bitstring {}", i + 1, bits)
+        }
+        let bits = match prefix_len {
+            Some(len) if len < bits.len() => &bits[..len],
+            _ => bits,
+        };
+        clusters.insert(word.to_string(), bits.to_string());
+    }
+    Ok(clusters)
+}
+
+fn offset_suffix(offset: isize) -> String {
+    match offset {
+        0 => String::new(),
+        o if o > 0 => format!("[+{}]", o),
+        o => format!("[{}]", o),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{load_brown_clusters, shape, Feature, FeatureExtractor};
+    use crate::tests::file_path;
+    use crate::{Gazetteer, Model, Tagger, TaggingScheme};
+
+    fn attrs(extractor: &FeatureExtractor, tokens: &[&str]) -> Vec<Vec<String>> {
+        extractor
+            .extract(tokens)
+            .into_iter()
+            .map(|item| item.into_iter().map(|a| a.attr).collect())
+            .collect()
+    }
+
+    #[test]
+    fn shape_works() {
+        assert_eq!(shape("hello"), "xxx");
+        assert_eq!(shape("Hello"), "Xxx");
+        assert_eq!(shape("HELLO"), "XXX");
+        assert_eq!(shape("McDonald"), "xX");
+        assert_eq!(shape("42"), "xX");
+    }
+
+    #[test]
+    fn feature_extractor_works() {
+        let mut clusters = HashMap::new();
+        clusters.insert("paris".to_string(), "0110".to_string());
+
+        let mut extractor = FeatureExtractor::new();
+        extractor.add(Feature::IsFirst, &[0, -1]);
+        extractor.add(Feature::IsDigit, &[0]);
+        extractor.add(Feature::Prefix { n: 2 }, &[0]);
+        extractor.add(Feature::Suffix { n: 3 }, &[0]);
+        extractor.add(
+            Feature::WordCluster {
+                name: "brown_clusters".to_string(),
+                clusters,
+            },
+            &[1],
+        );
+
+        assert_eq!(
+            attrs(&extractor, &["To", "Paris", "2"]),
+            vec![
+                vec![
+                    "is_first:1",
+                    "prefix_2:to",
+                    "suffix_3:to",
+                    "word_cluster_brown_clusters[+1]:0110"
+                ],
+                vec!["is_first[-1]:1", "prefix_2:pa", "suffix_3:ris"],
+                vec!["is_digit:1", "prefix_2:2", "suffix_3:2"],
+            ]
+        );
+        assert!(extractor.extract::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn feature_extractor_matches_model_attributes() {
+        let model = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let model_attributes = model.attributes().collect::<Vec<_>>();
+
+        let common_words = ["four", "me", "two"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        let mut extractor = FeatureExtractor::new();
+        extractor.add(Feature::IsFirst, &[0, -1, -2]);
+        extractor.add(Feature::IsLast, &[0, 1, 2]);
+        extractor.add(
+            Feature::Ngram {
+                n: 1,
+                common_words: Some(common_words),
+            },
+            &[0, -1, 1, 2],
+        );
+        extractor.add(Feature::ShapeNgram { n: 1 }, &[0]);
+        extractor.add(Feature::ShapeNgram { n: 2 }, &[0, -1]);
+
+        let attributes = attrs(&extractor, &["Give", "four", "apples"]);
+
+        assert_eq!(
+            attributes[0],
+            vec![
+                "is_first:1",
+                "is_last[+2]:1",
+                "ngram_1:rare_word",
+                "ngram_1[+1]:four",
+                "ngram_1[+2]:rare_word",
+                "shape_ngram_1:Xxx",
+                "shape_ngram_2:Xxx xxx",
+            ]
+        );
+        assert!(attributes[1].contains(&"shape_ngram_2[-1]:Xxx xxx".to_string()));
+        assert!(attributes[2].contains(&"is_first[-2]:1".to_string()));
+        for attribute in attributes.iter().flatten() {
+            assert!(
+                model_attributes.contains(&attribute.as_str()),
+                "{} is not in the model",
+                attribute
+            );
+        }
+    }
+
+    #[test]
+    fn load_brown_clusters_works() {
+        let data = "0110\tparis\t42\n\n111\tlondon\t3\n01\tberlin\n";
+
+        let clusters = load_brown_clusters(data.as_bytes(), None).unwrap();
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters["paris"], "0110");
+
+        let clusters = load_brown_clusters(data.as_bytes(), Some(2)).unwrap();
+        assert_eq!(clusters["paris"], "01");
+        assert_eq!(clusters["london"], "11");
+        assert_eq!(clusters["berlin"], "01");
+
+        assert!(load_brown_clusters("paris\n".as_bytes(), None).is_err());
+        assert!(load_brown_clusters("01a\tparis\n".as_bytes(), None).is_err());
+    }
+
+    #[test]
+    fn gazetteer_feature_works() {
+        let clusters = "11101011011101\tfour\n01010011100\tme\n".as_bytes();
+        let mut numbers = Gazetteer::new("built-in-snips/number", TaggingScheme::Bilou);
+        numbers.add(&["four"]);
+
+        let mut extractor = FeatureExtractor::new();
+        extractor.add(Feature::Gazetteer(numbers), &[0, -1]);
+        extractor.add(
+            Feature::WordCluster {
+                name: "brown_clusters".to_string(),
+                clusters: load_brown_clusters(clusters, None).unwrap(),
+            },
+            &[0],
+        );
+
+        let input = extractor.extract(&["Give", "four", "apples"]);
+        let attributes = input
+            .iter()
+            .map(|item| item.iter().map(|a| a.attr.as_str()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attributes,
+            vec![
+                vec![],
+                vec![
+                    "built-in-snips/number:U-",
+                    "word_cluster_brown_clusters:11101011011101"
+                ],
+                vec!["built-in-snips/number[-1]:U-"],
+            ]
+        );
+
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let model_attributes = tagger.model().unwrap().attributes().collect::<Vec<_>>();
+        for attribute in attributes.iter().flatten() {
+            assert!(model_attributes.contains(attribute));
+        }
+        assert_eq!(tagger.tag(&input).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use anyhow::Result;
+
+use crate::{SimpleAttribute, TaggingScheme};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Node {
+    children: HashMap<String, Node>,
+    is_entry: bool,
+}
+
+/// Entries of an entity, matched on the lowercased tokens of sentences. Each token of a match
+/// gets an attribute named after the gazetteer, valued with its tag in the scheme, such as
+/// `built-in-snips/number:U-`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gazetteer {
+    name: String,
+    scheme: TaggingScheme,
+    root: Node,
+}
+
+impl Gazetteer {
+    pub fn new(name: &str, scheme: TaggingScheme) -> Gazetteer {
+        Gazetteer {
+            name: name.to_string(),
+            scheme,
+            root: Node::default(),
+        }
+    }
+
+    /// Reads the entries of a gazetteer, one per line, their tokens being separated by spaces
+    pub fn from_reader<R: BufRead>(
+        name: &str,
+        scheme: TaggingScheme,
+        reader: R,
+    ) -> Result<Gazetteer> {
+        let mut gazetteer = Gazetteer::new(name, scheme);
+        for line in reader.lines() {
+            let line = line?;
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            gazetteer.add(&tokens);
+        }
+        Ok(gazetteer)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds an entry, empty entries being ignored
+    pub fn add<S: AsRef<str>>(&mut self, entry: &[S]) {
+        if entry.is_empty() {
+            return;
+        }
+        let mut node = &mut self.root;
+        for token in entry {
+            node = node
+                .children
+                .entry(token.as_ref().to_lowercase())
+                .or_default();
+        }
+        node.is_entry = true;
+    }
+
+    /// Token ranges matching an entry, from left to right, the longest entry being kept when
+    /// several of them start at the same token
+    pub fn matches<S: AsRef<str>>(&self, tokens: &[S]) -> Vec<(usize, usize)> {
+        let tokens = tokens
+            .iter()
+            .map(|t| t.as_ref().to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut matches = vec![];
+        let mut start = 0;
+        while start < tokens.len() {
+            let mut node = &self.root;
+            let mut end = None;
+            for (i, token) in tokens[start..].iter().enumerate() {
+                match node.children.get(token) {
+                    Some(child) => node = child,
+                    None => break,
+                }
+                if node.is_entry {
+                    end = Some(start + i + 1);
+                }
+            }
+            match end {
+                Some(end) => {
+                    matches.push((start, end));
+                    start = end;
+                }
+                None => start += 1,
+            }
+        }
+        matches
+    }
+
+    /// Tag of each token in the scheme of the gazetteer, if it is part of a match
+    pub fn annotate<S: AsRef<str>>(&self, tokens: &[S]) -> Vec<Option<String>> {
+        let mut tags = vec![None; tokens.len()];
+        for (start, end) in self.matches(tokens) {
+            for (i, tag) in tags[start..end].iter_mut().enumerate() {
+                *tag = Some(self.scheme.prefix(i, end - start).to_string());
+            }
+        }
+        tags
+    }
+
+    /// Attributes of each token, which can be tagged directly or merged with other attributes
+    pub fn attributes<S: AsRef<str>>(&self, tokens: &[S]) -> Vec<Vec<SimpleAttribute>> {
+        self.annotate(tokens)
+            .into_iter()
+            .map(|tag| {
+                tag.map(|tag| SimpleAttribute {
+                    attr: format!("{}:{}", self.name, tag),
+                    value: 1.0,
+                })
+                .into_iter()
+                .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gazetteer;
+    use crate::tests::file_path;
+    use crate::{Tagger, TaggingScheme};
+
+    #[test]
+    fn gazetteer_works() {
+        let entries = "new york\nnew york city\nyork\nparis\n\n";
+        let gazetteer =
+            Gazetteer::from_reader("city", TaggingScheme::Bilou, entries.as_bytes()).unwrap();
+
+        let tokens = ["From", "New", "York", "City", "to", "Paris", "new"];
+        assert_eq!(gazetteer.matches(&tokens), vec![(1, 4), (5, 6)]);
+        assert_eq!(
+            gazetteer.annotate(&tokens),
+            vec![
+                None,
+                Some("B-".to_string()),
+                Some("I-".to_string()),
+                Some("L-".to_string()),
+                None,
+                Some("U-".to_string()),
+                None,
+            ]
+        );
+
+        let mut bio = Gazetteer::new("city", TaggingScheme::Bio);
+        bio.add(&["new", "york"]);
+        let attributes = bio.attributes(&["new", "york", "paris"]);
+        assert_eq!(attributes[0][0].attr, "city:B-");
+        assert_eq!(attributes[1][0].attr, "city:I-");
+        assert!(attributes[2].is_empty());
+    }
+
+    #[test]
+    fn gazetteer_attributes_can_be_tagged() {
+        let mut numbers = Gazetteer::new("built-in-snips/number", TaggingScheme::Bilou);
+        numbers.add(&["four"]);
+
+        let attributes = numbers.attributes(&["give", "four", "apples"]);
+        assert_eq!(attributes[1][0].attr, "built-in-snips/number:U-");
+
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        assert_eq!(tagger.tag(&attributes).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::os::raw::c_void;
+
+use anyhow::{bail, Result};
+
+use crate::{Item, SimpleAttribute};
+
+/// Hashing of the attributes into a fixed number of buckets, bounding the size of the models
+/// trained on attributes with a huge cardinality, such as character ngrams
+///
+/// Attributes are hashed with lookup3, as the keys of the dictionaries of the models, and
+/// replaced by the name of their bucket, `#<bucket>`. The same hashing must be used for training
+/// and tagging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureHashing {
+    num_buckets: u32,
+}
+
+impl FeatureHashing {
+    pub fn new(num_buckets: u32) -> Result<FeatureHashing> {
+        if num_buckets == 0 {
+            bail!("feature hashing needs at least one bucket")
+        }
+        Ok(FeatureHashing { num_buckets })
+    }
+
+    pub fn num_buckets(&self) -> u32 {
+        self.num_buckets
+    }
+
+    pub fn bucket(&self, attr: &[u8]) -> u32 {
+        let hash =
+            unsafe { crfsuite_sys::hashlittle(attr.as_ptr() as *const c_void, attr.len(), 0) };
+        hash % self.num_buckets
+    }
+
+    /// Name of the bucket of an attribute
+    pub fn hashed_name(&self, attr: &[u8]) -> String {
+        format!("#{}", self.bucket(attr))
+    }
+
+    /// Hashes the attributes of each item, the values of the attributes falling in the same
+    /// bucket being summed
+    pub fn hash<I: Item>(&self, input: &[I]) -> Result<Vec<Vec<SimpleAttribute>>> {
+        let mut buffer = vec![];
+        input
+            .iter()
+            .map(|item| {
+                let mut hashed: Vec<SimpleAttribute> = vec![];
+                let mut positions: HashMap<u32, usize> = HashMap::new();
+                item.for_each_attribute(|attribute| {
+                    buffer.clear();
+                    attribute.write_attr(&mut buffer);
+                    let bucket = self.bucket(&buffer);
+                    match positions.get(&bucket) {
+                        Some(&i) => hashed[i].value += attribute.get_value(),
+                        None => {
+                            positions.insert(bucket, hashed.len());
+                            hashed.push(SimpleAttribute {
+                                attr: format!("#{}", bucket),
+                                value: attribute.get_value(),
+                            });
+                        }
+                    }
+                    Ok(())
+                })?;
+                Ok(hashed)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FeatureHashing;
+    use crate::{ModelWriter, SimpleAttribute, Tagger};
+
+    #[test]
+    fn hashing_works() {
+        assert!(FeatureHashing::new(0).is_err());
+
+        let hashing = FeatureHashing::new(16).unwrap();
+        // lookup3 of an empty key with a null seed
+        assert_eq!(
+            unsafe { crfsuite_sys::hashlittle(b"".as_ptr() as *const _, 0, 0) },
+            0xdeadbeef
+        );
+        assert!(hashing.bucket(b"ngram_1:set") < 16);
+        assert_eq!(
+            hashing.bucket(b"ngram_1:set"),
+            hashing.bucket(b"ngram_1:set")
+        );
+
+        let single = FeatureHashing::new(1).unwrap();
+        let input = vec![vec![
+            ("ngram_1".to_string(), "set".to_string()),
+            ("is_first".to_string(), "1".to_string()),
+        ]];
+        assert_eq!(
+            single.hash(&input).unwrap(),
+            vec![vec![SimpleAttribute {
+                attr: "#0".to_string(),
+                value: 2.0,
+            }]]
+        );
+    }
+
+    #[test]
+    fn tagger_with_hashing_works() {
+        let hashing = FeatureHashing::new(1 << 20).unwrap();
+        let mut writer = ModelWriter::new();
+        writer.set_state_feature(&hashing.hashed_name(b"is_digit"), "NUM", 2.0);
+        writer.set_state_feature(&hashing.hashed_name(b"is_word"), "O", 1.0);
+        let bytes = writer.write_to_bytes().unwrap();
+
+        let input = vec![
+            vec![SimpleAttribute {
+                attr: "is_word".to_string(),
+                value: 1.0,
+            }],
+            vec![SimpleAttribute {
+                attr: "is_digit".to_string(),
+                value: 1.0,
+            }],
+        ];
+
+        let mut tagger = Tagger::create_from_memory(bytes).unwrap();
+        tagger.set_feature_hashing(Some(hashing));
+        assert_eq!(tagger.tag(&input).unwrap(), vec!["O", "NUM"]);
+
+        let hashed = hashing.hash(&input).unwrap();
+        tagger.set_feature_hashing(None);
+        assert_eq!(tagger.tag(&hashed).unwrap(), vec!["O", "NUM"]);
+    }
+}
@@ -1,7 +1,9 @@
+use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
 use std::f64;
 use std::ffi::{CStr, CString};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::mem::zeroed;
 use std::os::raw::{c_char, c_int};
 use std::path::Path;
@@ -12,20 +14,94 @@ use anyhow::{bail, Result};
 use crfsuite_sys::crfsuite_create_instance_from_memory;
 use crfsuite_sys::floatval_t;
 
-#[derive(Debug)]
+mod analysis;
+pub mod compat;
+pub mod conll;
+mod data;
+mod diff;
+mod ensemble;
+mod evaluation;
+pub mod features;
+mod gazetteer;
+mod hashing;
+mod model;
+mod online;
+mod quantize;
+mod reload;
+mod scheme;
+pub mod text;
+mod trainer;
+mod writer;
+
+pub use crate::analysis::{ConfusedPair, ErrorAnalysis, ErrorReport, SequenceError};
+pub use crate::data::{ModelData, StateFeature, Transition};
+pub use crate::diff::{ModelDiff, WeightChange};
+pub use crate::ensemble::EnsembleTagger;
+pub use crate::evaluation::{
+    EntityEvaluation, Evaluation, EvaluationReport, LabelEvaluation, SpanEvaluation, SpanMatching,
+    SpanReport,
+};
+pub use crate::gazetteer::Gazetteer;
+pub use crate::hashing::FeatureHashing;
+pub use crate::model::Model;
+pub use crate::online::{OnlineAlgorithm, OnlineTrainer};
+pub use crate::quantize::Quantization;
+pub use crate::reload::{PooledTagger, ReloadableModel};
+pub use crate::scheme::{spans, LabelFormat, Span, TaggedSpan, TaggingScheme};
+pub use crate::trainer::{Algorithm, Trainer};
+pub use crate::writer::ModelWriter;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct SimpleAttribute {
     pub attr: String,
     pub value: f64,
 }
 
+/// Attribute of an item, made of a name and a value
 pub trait Attribute {
-    fn get_attr(&self) -> Result<CString>;
+    /// Appends the name of the attribute to `buffer`, which lets callers reuse a single buffer
+    /// instead of allocating a string for each attribute
+    fn write_attr(&self, buffer: &mut Vec<u8>);
     fn get_value(&self) -> f64;
 }
 
+/// Item of a sequence, that is anything whose references iterate over attributes, such as
+/// `Vec<SimpleAttribute>`, `Vec<&str>` or `HashMap<String, f64>`
+pub trait Item {
+    fn for_each_attribute<F>(&self, f: F) -> Result<()>
+    where
+        F: FnMut(&dyn Attribute) -> Result<()>;
+}
+
+impl<T: ?Sized> Item for T
+where
+    for<'a> &'a T: IntoIterator,
+    for<'a> <&'a T as IntoIterator>::Item: Attribute,
+{
+    fn for_each_attribute<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&dyn Attribute) -> Result<()>,
+    {
+        for attribute in self {
+            f(&attribute)?;
+        }
+        Ok(())
+    }
+}
+
+impl<A: Attribute + ?Sized> Attribute for &A {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        (**self).write_attr(buffer)
+    }
+
+    fn get_value(&self) -> f64 {
+        (**self).get_value()
+    }
+}
+
 impl Attribute for SimpleAttribute {
-    fn get_attr(&self) -> Result<CString> {
-        Ok(CString::new(self.attr.as_bytes())?)
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.attr.as_bytes());
     }
 
     fn get_value(&self) -> f64 {
@@ -33,10 +109,52 @@ impl Attribute for SimpleAttribute {
     }
 }
 
+impl Attribute for str {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.as_bytes());
+    }
+
+    fn get_value(&self) -> f64 {
+        1.0
+    }
+}
+
+impl Attribute for String {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.as_bytes());
+    }
+
+    fn get_value(&self) -> f64 {
+        1.0
+    }
+}
+
+impl Attribute for Cow<'_, str> {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.as_bytes());
+    }
+
+    fn get_value(&self) -> f64 {
+        1.0
+    }
+}
+
 impl Attribute for (String, String) {
-    fn get_attr(&self) -> Result<CString> {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        #[allow(clippy::needless_borrowed_reference)]
         let &(ref key, ref value) = self;
-        Ok(CString::new(format!("{}:{}", key, value).as_bytes())?)
+        write_key_value(buffer, key, value);
+    }
+
+    fn get_value(&self) -> f64 {
+        1.0
+    }
+}
+
+impl Attribute for (&str, &str) {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        let (key, value) = self;
+        write_key_value(buffer, key, value);
     }
 
     fn get_value(&self) -> f64 {
@@ -44,12 +162,121 @@ impl Attribute for (String, String) {
     }
 }
 
+impl Attribute for (&str, f64) {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.0.as_bytes());
+    }
+
+    fn get_value(&self) -> f64 {
+        self.1
+    }
+}
+
+impl Attribute for (String, f64) {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.0.as_bytes());
+    }
+
+    fn get_value(&self) -> f64 {
+        self.1
+    }
+}
+
+// entries of a `HashMap<String, f64>`
+impl Attribute for (&String, &f64) {
+    fn write_attr(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.0.as_bytes());
+    }
+
+    fn get_value(&self) -> f64 {
+        *self.1
+    }
+}
+
+fn write_key_value(buffer: &mut Vec<u8>, key: &str, value: &str) {
+    buffer.extend_from_slice(key.as_bytes());
+    buffer.push(b':');
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// Name of an attribute written in `buffer`, as expected by the C dictionaries
+pub(crate) fn attr_to_c_str(buffer: &mut Vec<u8>) -> Result<&CStr> {
+    if buffer.contains(&0) {
+        bail!(
+            "invalid attribute {:?} : nul byte found",
+            String::from_utf8_lossy(buffer)
+        )
+    }
+    buffer.push(0);
+    Ok(CStr::from_bytes_with_nul(buffer)?)
+}
+
 pub struct Tagger {
-    #[allow(unused)]
-    // we own the bytes here that is used in the C code
-    bytes: Vec<u8>,
     model: ModelWrapper,
     tagger: TaggerWrapper,
+    // active attributes of each item of the last instance, used to explain the predictions
+    attributes: RefCell<Vec<Vec<(c_int, f64)>>>,
+    hashing: Option<FeatureHashing>,
+    unknown_attributes: UnknownAttributes,
+    // coverage of the attributes of each item of the last instance
+    coverage: RefCell<Vec<TokenCoverage>>,
+    // built on first use only, as reading the model validates all its strings
+    crf1dm: OnceCell<Model>,
+    // we own the bytes here that is used in the C code, keep them last so that they are dropped
+    // after the C model and tagger
+    bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub label: String,
+    /// The label with the highest marginal probability after the predicted one
+    pub runner_up: Option<String>,
+    /// The active attributes, sorted by decreasing absolute margin between the label and the
+    /// runner up
+    pub contributions: Vec<Contribution>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contribution {
+    pub attribute: String,
+    pub value: f64,
+    /// Weighted score added to the predicted label
+    pub label_weight: f64,
+    /// Weighted score added to the runner up label
+    pub runner_up_weight: f64,
+}
+
+/// What the tagger does with the attributes that are unknown to the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownAttributes {
+    /// Unknown attributes are dropped, only being counted
+    Ignore,
+    /// Unknown attributes are dropped, and their names are kept in the coverage of the items
+    Collect,
+    /// Setting an instance with an unknown attribute fails
+    Fail,
+}
+
+/// Coverage of the attributes of an item by the model
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenCoverage {
+    pub num_known: usize,
+    pub num_unknown: usize,
+    /// Names of the unknown attributes, only collected with `UnknownAttributes::Collect`
+    pub unknown: Vec<String>,
+}
+
+impl TokenCoverage {
+    /// Ratio of the attributes known to the model, 1 for an item without attributes
+    pub fn ratio(&self) -> f64 {
+        let total = self.num_known + self.num_unknown;
+        if total == 0 {
+            1.0
+        } else {
+            self.num_known as f64 / total as f64
+        }
+    }
 }
 
 impl Tagger {
@@ -62,6 +289,7 @@ impl Tagger {
     }
 
     pub fn create_from_memory(data: Vec<u8>) -> Result<Tagger> {
+        let data = model::prepare_bytes(data)?;
         let mut model = null_mut();
 
         let r = unsafe {
@@ -91,10 +319,38 @@ impl Tagger {
         Ok(Tagger {
             model,
             tagger: TaggerWrapper { tagger },
+            attributes: RefCell::new(vec![]),
+            hashing: None,
+            unknown_attributes: UnknownAttributes::Ignore,
+            coverage: RefCell::new(vec![]),
+            crf1dm: OnceCell::new(),
             bytes: data,
         })
     }
 
+    /// Hashes the attributes of the inputs, for models trained on hashed attributes
+    pub fn set_feature_hashing(&mut self, hashing: Option<FeatureHashing>) {
+        self.hashing = hashing;
+    }
+
+    pub fn set_unknown_attributes(&mut self, unknown_attributes: UnknownAttributes) {
+        self.unknown_attributes = unknown_attributes;
+    }
+
+    /// Coverage of the attributes of each item of the last instance
+    pub fn coverage(&self) -> Vec<TokenCoverage> {
+        self.coverage.borrow().clone()
+    }
+
+    /// Weights of the model, read on the first call
+    pub fn model(&self) -> Result<&Model> {
+        if let Some(model) = self.crf1dm.get() {
+            return Ok(model);
+        }
+        let model = Model::create_from_memory(self.bytes.clone())?;
+        Ok(self.crf1dm.get_or_init(|| model))
+    }
+
     pub fn labels(&self) -> Result<Vec<String>> {
         let mut labels = null_mut();
 
@@ -123,18 +379,92 @@ impl Tagger {
         Ok(lseq)
     }
 
-    pub fn tag<A: Attribute>(&self, input: &[Vec<A>]) -> Result<Vec<String>> {
+    pub fn tag<I: Item>(&self, input: &[I]) -> Result<Vec<String>> {
         self.set(input)?;
         self.viterbi()
     }
 
-    pub fn set<A: Attribute>(&self, input: &[Vec<A>]) -> Result<()> {
+    /// Tags the input and decodes the entities of the predicted labels, each with the marginal
+    /// probability of its labels
+    pub fn tag_spans<I: Item>(&self, input: &[I], format: &LabelFormat) -> Result<Vec<TaggedSpan>> {
+        self.set(input)?;
+        let path = self.viterbi_path()?;
+        let labels = self.path_labels(&path)?;
+
+        format
+            .spans(&labels)
+            .into_iter()
+            .map(|span| {
+                let mut probability = f64::NAN;
+                let r = self.tagger.marginal_path(
+                    path.as_ptr(),
+                    span.start as c_int,
+                    span.end as c_int,
+                    &mut probability,
+                );
+                if r != 0 {
+                    bail!("failed to compute the marginal probability of a span")
+                }
+                Ok(TaggedSpan {
+                    label: span.entity,
+                    start: span.start,
+                    end: span.end,
+                    probability,
+                })
+            })
+            .collect()
+    }
+
+    pub fn set<I: Item>(&self, input: &[I]) -> Result<()> {
         let mut attrs = null_mut();
         let r = self.model.get_attrs(&mut attrs);
         if r != 0 {
             bail!("error while getting tagger : non zero C return code...")
         }
         let attrs = DictionaryWrapper { dict: attrs };
+
+        // the resolved input replaces the previous one only once the C tagger has accepted it
+        let mut attributes = Vec::with_capacity(input.len());
+        let mut coverage = Vec::with_capacity(input.len());
+
+        // a single buffer holds the name of every attribute
+        let mut buffer = vec![];
+        for (i, item) in input.iter().enumerate() {
+            let mut item_attributes = vec![];
+            let mut item_coverage = TokenCoverage::default();
+
+            item.for_each_attribute(|attribute| {
+                buffer.clear();
+                attribute.write_attr(&mut buffer);
+                if let Some(hashing) = self.hashing {
+                    let bucket = hashing.bucket(&buffer);
+                    buffer.clear();
+                    write!(buffer, "#{}", bucket)?;
+                }
+                let aid = attrs.str_to_id(attr_to_c_str(&mut buffer)?.as_ptr());
+                if 0 <= aid {
+                    item_attributes.push((aid, attribute.get_value()));
+                    item_coverage.num_known += 1;
+                    return Ok(());
+                }
+
+                item_coverage.num_unknown += 1;
+                if self.unknown_attributes != UnknownAttributes::Ignore {
+                    // the buffer may hold the hashed name
+                    let mut name = vec![];
+                    attribute.write_attr(&mut name);
+                    let name = String::from_utf8_lossy(&name).into_owned();
+                    if self.unknown_attributes == UnknownAttributes::Fail {
+                        bail!("unknown attribute {} at position {}", name, i)
+                    }
+                    item_coverage.unknown.push(name);
+                }
+                Ok(())
+            })?;
+            attributes.push(item_attributes);
+            coverage.push(item_coverage);
+        }
+
         let mut inst = unsafe { zeroed() };
 
         unsafe {
@@ -143,24 +473,13 @@ impl Tagger {
 
         let inst_items = unsafe { slice::from_raw_parts_mut(inst.items, inst.num_items as usize) };
 
-        for (i, item) in input.iter().enumerate() {
-            let inst_item = &mut inst_items[i];
-
+        for (inst_item, item_attributes) in inst_items.iter_mut().zip(attributes.iter()) {
             unsafe { crfsuite_sys::crfsuite_item_init(inst_item) };
 
-            for inner_item in item.iter() {
-                let raw_pointer = inner_item.get_attr()?.into_raw();
-                let aid = attrs.str_to_id(raw_pointer);
-
-                if 0 <= aid {
-                    let cont = &mut unsafe { zeroed() };
-                    unsafe {
-                        crfsuite_sys::crfsuite_attribute_set(cont, aid, inner_item.get_value())
-                    };
-                    unsafe { crfsuite_sys::crfsuite_item_append_attribute(inst_item, cont) };
-                }
-
-                let _ = unsafe { CString::from_raw(raw_pointer) }; // get back the string to free it
+            for &(aid, value) in item_attributes {
+                let cont = &mut unsafe { zeroed() };
+                unsafe { crfsuite_sys::crfsuite_attribute_set(cont, aid, value) };
+                unsafe { crfsuite_sys::crfsuite_item_append_attribute(inst_item, cont) };
             }
         }
 
@@ -177,11 +496,18 @@ impl Tagger {
             crfsuite_sys::crfsuite_instance_finish(&mut inst);
         }
 
+        *self.attributes.borrow_mut() = attributes;
+        *self.coverage.borrow_mut() = coverage;
         Ok(())
     }
 
     pub fn viterbi(&self) -> Result<Vec<String>> {
-        let t: usize = self.tagger.length() as usize;
+        let path = self.viterbi_path()?;
+        self.path_labels(&path)
+    }
+
+    fn path_labels(&self, path: &[c_int]) -> Result<Vec<String>> {
+        let t = path.len();
         if t == 0 {
             return Ok(vec![]);
         }
@@ -196,17 +522,9 @@ impl Tagger {
 
         let labels = DictionaryWrapper { dict: labels };
 
-        let mut score = f64::NAN;
-        let mut path = vec![0; t];
-
-        let r = self.tagger.viterbi(&mut path[0], &mut score);
-        if r != 0 {
-            bail!("failed to find the viterbi path")
-        }
-
         let mut yseq = Vec::with_capacity(t);
 
-        for p in path.into_iter().take(t) {
+        for &p in path {
             let mut label = null();
             let r = labels.id_to_string(p, &mut label);
             if r != 0 {
@@ -270,9 +588,116 @@ impl Tagger {
         Ok((score - lognorm).exp())
     }
 
-    /*pub fn marginal(&self, label: &str, position: usize) -> f64 {
-        unimplemented!();
-    }*/
+    /// Explains the prediction at the given position of the current instance, listing the
+    /// contribution of each active attribute to the predicted label and to the runner up
+    pub fn explain(&self, position: usize) -> Result<Explanation> {
+        let path = self.viterbi_path()?;
+        if position >= path.len() {
+            bail!(
+                "position {} is out of the instance of length {}",
+                position,
+                path.len()
+            )
+        }
+
+        let model = self.model()?;
+        let lid = path[position];
+        let mut runner_up = None;
+        let mut runner_up_prob = f64::NEG_INFINITY;
+
+        for l in 0..model.num_labels() as c_int {
+            if l == lid {
+                continue;
+            }
+            let mut prob = f64::NAN;
+            let r = self.tagger.marginal_point(l, position as c_int, &mut prob);
+            if r != 0 {
+                bail!("failed to compute the marginal probability")
+            }
+            if prob > runner_up_prob {
+                runner_up = Some(l);
+                runner_up_prob = prob;
+            }
+        }
+
+        let mut contributions = self.attributes.borrow()[position]
+            .iter()
+            .map(|&(aid, value)| {
+                let mut contribution = Contribution {
+                    attribute: model.attr(aid).to_string(),
+                    value,
+                    label_weight: 0.0,
+                    runner_up_weight: 0.0,
+                };
+                for (l, weight) in model.attr_weights(aid) {
+                    if l == lid {
+                        contribution.label_weight = value * weight;
+                    } else if Some(l) == runner_up {
+                        contribution.runner_up_weight = value * weight;
+                    }
+                }
+                contribution
+            })
+            .collect::<Vec<_>>();
+
+        contributions.sort_by(|a, b| {
+            let margin_a = (a.label_weight - a.runner_up_weight).abs();
+            let margin_b = (b.label_weight - b.runner_up_weight).abs();
+            margin_b.total_cmp(&margin_a)
+        });
+
+        Ok(Explanation {
+            label: model.label(lid).to_string(),
+            runner_up: runner_up.map(|l| model.label(l).to_string()),
+            contributions,
+        })
+    }
+
+    fn viterbi_path(&self) -> Result<Vec<c_int>> {
+        let t: usize = self.tagger.length() as usize;
+        if t == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut score = f64::NAN;
+        let mut path = vec![0; t];
+
+        let r = self.tagger.viterbi(&mut path[0], &mut score);
+        if r != 0 {
+            bail!("failed to find the viterbi path")
+        }
+
+        Ok(path)
+    }
+
+    /// Marginal probability of the label at the given position of the current instance
+    pub fn marginal(&self, label: &str, position: usize) -> Result<f64> {
+        let t = self.tagger.length() as usize;
+        if position >= t {
+            bail!(
+                "position {} is out of the instance of length {}",
+                position,
+                t
+            )
+        }
+        let mut labels = null_mut();
+        let r = self.model.get_labels(&mut labels);
+        if r != 0 {
+            bail!("failed to obtain the dictionary interface for labels")
+        }
+        let labels = DictionaryWrapper { dict: labels };
+        let l = labels.str_to_id(CString::new(label)?.as_ptr());
+        if l < 0 {
+            bail!("unknown label : {}", label)
+        }
+
+        let mut prob = f64::NAN;
+        let r = self.tagger.marginal_point(l, position as c_int, &mut prob);
+        if r != 0 {
+            bail!("failed to compute the marginal probability")
+        }
+        Ok(prob)
+    }
 }
 
 struct DictionaryWrapper {
@@ -394,6 +819,32 @@ impl TaggerWrapper {
             }
         }
     }
+
+    fn marginal_point(&self, l: c_int, t: c_int, ptr_prob: *mut floatval_t) -> c_int {
+        unsafe {
+            if let Some(marginal_point) = (*self.tagger).marginal_point {
+                marginal_point(self.tagger, l, t, ptr_prob)
+            } else {
+                panic!("no callback for marginal_point")
+            }
+        }
+    }
+
+    fn marginal_path(
+        &self,
+        path: *const c_int,
+        begin: c_int,
+        end: c_int,
+        ptr_prob: *mut floatval_t,
+    ) -> c_int {
+        unsafe {
+            if let Some(marginal_path) = (*self.tagger).marginal_path {
+                marginal_path(self.tagger, path, begin, end, ptr_prob)
+            } else {
+                panic!("no callback for marginal_path")
+            }
+        }
+    }
 }
 
 impl Drop for TaggerWrapper {
@@ -461,6 +912,10 @@ impl Drop for ModelWrapper {
 mod tests {
     use super::SimpleAttribute;
     use super::Tagger;
+    use super::UnknownAttributes;
+    use super::{LabelFormat, TaggingScheme};
+    use std::borrow::Cow;
+    use std::collections::HashMap;
     use std::env;
     use std::fs::File;
     use std::io::Read;
@@ -1289,6 +1744,180 @@ mod tests {
         assert_eq!(r, vec!["O"]);
     }
 
+    #[test]
+    fn non_utf8_attributes_work() {
+        let mut bytes = std::fs::read(file_path("modelo62R_B.crfsuite")).unwrap();
+        let position = bytes.windows(10).position(|w| w == b"ngram_1:me").unwrap();
+        bytes[position + 9] = 0xff;
+
+        let t = Tagger::create_from_memory(bytes).unwrap();
+        assert_eq!(t.tag(&[vec!["is_first:1"]]).unwrap(), vec!["O"]);
+        assert!(t.marginal("O", 0).is_ok());
+        assert!(t.model().is_err());
+    }
+
+    #[test]
+    fn attribute_impls_work() {
+        let t = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let expected = vec!["O", "B-snips/number", "I-snips/number"];
+
+        let strs: Vec<Vec<&str>> = vec![
+            vec![
+                "is_first:1",
+                "ngram_1:me",
+                "ngram_1[+1]:two",
+                "shape_ngram_1:xxx",
+            ],
+            vec!["ngram_1:two", "built-in-snips/number:B-", "ngram_1[-1]:me"],
+            vec!["built-in-snips/number:L-", "ngram_1[-1]:two", "is_last:1"],
+        ];
+        assert_eq!(t.tag(&strs).unwrap(), expected);
+
+        let cows = strs
+            .iter()
+            .map(|item| item.iter().map(|&a| Cow::from(a)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(t.tag(&cows).unwrap(), expected);
+
+        let weighted = strs
+            .iter()
+            .map(|item| item.iter().map(|&a| (a, 1.0)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(t.tag(&weighted).unwrap(), expected);
+
+        let key_values = strs
+            .iter()
+            .map(|item| {
+                item.iter()
+                    .map(|a| {
+                        let i = a.find(':').unwrap();
+                        (&a[..i], &a[i + 1..])
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(t.tag(&key_values).unwrap(), expected);
+
+        let maps = strs
+            .iter()
+            .map(|item| {
+                item.iter()
+                    .map(|&a| (a.to_string(), 1.0))
+                    .collect::<HashMap<String, f64>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(t.tag(&maps).unwrap(), expected);
+
+        assert!(t.tag(&[vec!["is_first\u{0}1"]]).is_err());
+    }
+
+    #[test]
+    fn unknown_attributes_work() {
+        let mut t = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let input = vec![
+            vec!["is_first:1", "ngram_1:me", "ngram_1:drift"],
+            vec!["is_last:1"],
+            vec![],
+        ];
+
+        t.tag(&input).unwrap();
+        let coverage = t.coverage();
+        assert_eq!(coverage.len(), 3);
+        assert_eq!((coverage[0].num_known, coverage[0].num_unknown), (2, 1));
+        assert!(coverage[0].unknown.is_empty());
+        assert!((coverage[0].ratio() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(coverage[1].ratio(), 1.0);
+        assert_eq!(coverage[2].ratio(), 1.0);
+
+        t.set_unknown_attributes(UnknownAttributes::Collect);
+        t.tag(&input).unwrap();
+        assert_eq!(t.coverage()[0].unknown, vec!["ngram_1:drift"]);
+
+        t.set_unknown_attributes(UnknownAttributes::Fail);
+        let error = t.tag(&input).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown attribute ngram_1:drift at position 0"
+        );
+        // a rejected input leaves the previous one in place
+        assert!(t.tag(&input[..1]).is_err());
+        assert_eq!(t.coverage()[0].unknown, vec!["ngram_1:drift"]);
+        assert!(t.explain(2).is_ok());
+        assert!(t.tag(&input[1..]).is_ok());
+    }
+
+    #[test]
+    fn explain_works() {
+        let t = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+
+        let input = vec![
+            vec![
+                ("is_first".to_string(), "1".to_string()),
+                ("ngram_1".to_string(), "rare_word".to_string()),
+                ("built-in-snips/number[+1]".to_string(), "U-".to_string()),
+            ],
+            vec![
+                ("ngram_1".to_string(), "rare_word".to_string()),
+                ("built-in-snips/number".to_string(), "U-".to_string()),
+                ("is_last".to_string(), "1".to_string()),
+            ],
+        ];
+
+        let tags = t.tag(&input).unwrap();
+        let explanation = t.explain(1).unwrap();
+
+        assert_eq!(explanation.label, tags[1]);
+        assert_eq!(explanation.runner_up, Some("B-snips/number".to_string()));
+        assert_eq!(explanation.contributions.len(), 3);
+        assert_eq!(explanation.contributions[0].attribute, "is_last:1");
+
+        let number = explanation
+            .contributions
+            .iter()
+            .find(|c| c.attribute == "built-in-snips/number:U-")
+            .unwrap();
+        assert!(number.runner_up_weight > number.label_weight);
+        assert!(t.explain(2).is_err());
+
+        // values come from the caller and may not be numbers
+        let input = vec![vec![("is_first:1", 1.0), ("ngram_1:rare_word", f64::NAN)]];
+        t.tag(&input).unwrap();
+        assert_eq!(t.explain(0).unwrap().contributions.len(), 2);
+    }
+
+    #[test]
+    fn tag_spans_works() {
+        let t = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+
+        let input = vec![
+            vec![
+                "is_first:1",
+                "ngram_1:me",
+                "ngram_1[+1]:two",
+                "shape_ngram_1:xxx",
+            ],
+            vec!["ngram_1:two", "built-in-snips/number:B-", "ngram_1[-1]:me"],
+            vec!["built-in-snips/number:L-", "ngram_1[-1]:two", "is_last:1"],
+        ];
+
+        let format = LabelFormat::new(TaggingScheme::Bio);
+        let spans = t.tag_spans(&input, &format).unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].label, "snips/number");
+        assert_eq!((spans[0].start, spans[0].end), (1, 3));
+
+        // the labels of the whole sequence are less likely than the ones of the span
+        let tags = t.tag(&input).unwrap();
+        let probability = t.probability(&tags).unwrap();
+        assert!(probability <= spans[0].probability && spans[0].probability <= 1.0);
+
+        let format = LabelFormat {
+            separator: "_".to_string(),
+            ..format
+        };
+        assert!(t.tag_spans(&input, &format).unwrap().is_empty());
+    }
+
     pub fn file_path(file_name: &str) -> path::PathBuf {
         if env::var("DINGHY").is_ok() {
             env::current_exe()
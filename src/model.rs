@@ -0,0 +1,658 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{Read, Write};
+use std::mem::zeroed;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use crfsuite_sys::{crf1dm_feature_t, feature_refs_t};
+
+use crate::diff::{diff, ModelDiff};
+use crate::ensemble::average;
+use crate::quantize::{dequantize, is_quantized, quantize, Quantization};
+use crate::writer::write_model;
+
+pub(crate) const HEADER_SIZE: usize = 48;
+pub(crate) const CHUNK_SIZE: usize = 12;
+pub(crate) const FEATURE_SIZE: usize = 20;
+
+/// Read only access to the internals of a crf1dm model: its labels, attributes and the weights
+/// of its transition and state features.
+pub struct Model {
+    model: Crf1dmWrapper,
+    // we own the bytes here that is used in the C code, keep them last so that they are dropped
+    // after the model
+    bytes: Vec<u8>,
+}
+
+impl Model {
+    pub fn create_from_file<P: AsRef<Path>>(path: P) -> Result<Model> {
+        let mut file = File::open(path)?;
+        let mut bytes = Vec::with_capacity(file.metadata()?.len() as usize);
+        file.read_to_end(&mut bytes)?;
+
+        Model::create_from_memory(bytes)
+    }
+
+    pub fn create_from_memory(data: Vec<u8>) -> Result<Model> {
+        let data = prepare_bytes(data)?;
+
+        let model = unsafe {
+            crfsuite_sys::crf1dm_new_from_memory(
+                data.as_ptr() as *const _,
+                data.len() as crfsuite_sys::size_t,
+            )
+        };
+
+        if model.is_null() {
+            bail!("error while reading model : null pointer returned by C code...")
+        }
+
+        let model = Model {
+            bytes: data,
+            model: Crf1dmWrapper { model },
+        };
+
+        for lid in 0..model.model.num_labels() {
+            if to_str(model.model.to_label(lid)).is_none() {
+                bail!("invalid label with id {} in model", lid)
+            }
+        }
+
+        for aid in 0..model.model.num_attrs() {
+            if to_str(model.model.to_attr(aid)).is_none() {
+                bail!("invalid attribute with id {} in model", aid)
+            }
+        }
+
+        Ok(model)
+    }
+
+    pub fn num_labels(&self) -> usize {
+        self.model.num_labels() as usize
+    }
+
+    pub fn num_attrs(&self) -> usize {
+        self.model.num_attrs() as usize
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.model.num_labels()).map(move |lid| self.label(lid))
+    }
+
+    pub fn attributes(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.model.num_attrs()).map(move |aid| self.attr(aid))
+    }
+
+    /// Iterates over the transition features as `(from_label, to_label, weight)`
+    pub fn transitions(&self) -> impl Iterator<Item = (&str, &str, f64)> + '_ {
+        (0..self.model.num_labels()).flat_map(move |lid| {
+            self.model
+                .label_features(lid)
+                .map(move |f| (self.label(f.src), self.label(f.dst), f.weight))
+        })
+    }
+
+    /// Iterates over the state features as `(attribute, label, weight)`
+    pub fn state_features(&self) -> impl Iterator<Item = (&str, &str, f64)> + '_ {
+        (0..self.model.num_attrs()).flat_map(move |aid| {
+            self.model
+                .attr_features(aid)
+                .map(move |f| (self.attr(f.src), self.label(f.dst), f.weight))
+        })
+    }
+
+    /// Returns the `k` state features with the largest absolute weight for the given label, as
+    /// `(attribute, weight)`
+    pub fn top_features(&self, label: &str, k: usize) -> Result<Vec<(&str, f64)>> {
+        let lid = self.label_id(label)?;
+
+        let mut features = (0..self.model.num_attrs())
+            .flat_map(|aid| self.model.attr_features(aid))
+            .filter(|f| f.dst == lid)
+            .map(|f| (self.attr(f.src), f.weight))
+            .collect::<Vec<_>>();
+
+        features.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        features.truncate(k);
+
+        Ok(features)
+    }
+
+    /// Builds a compact copy of the model without the state features whose absolute weight is
+    /// below `min_abs_weight`, nor the attributes left without any feature
+    pub fn prune(&self, min_abs_weight: f64) -> Result<Vec<u8>> {
+        let labels = self.labels().collect::<Vec<_>>();
+        let mut attrs = vec![];
+        let mut amap = vec![-1; self.num_attrs()];
+        let mut features = vec![];
+
+        for mut f in self.features() {
+            if f.type_ == crfsuite_sys::FT_STATE as c_int {
+                if f.weight.abs() < min_abs_weight {
+                    continue;
+                }
+                let aid = &mut amap[f.src as usize];
+                if *aid < 0 {
+                    *aid = attrs.len() as c_int;
+                    attrs.push(self.attr(f.src));
+                }
+                f.src = *aid;
+            }
+            features.push(f);
+        }
+
+        write_model(&labels, &attrs, &features)
+    }
+
+    /// Builds a copy of the model where the labels found in `map` are renamed, the other ones
+    /// keeping their name. Labels renamed to the same name are merged, the weights of the
+    /// features they had in common being summed.
+    pub fn rename_labels<K, V>(&self, map: &HashMap<K, V>) -> Result<Vec<u8>>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: AsRef<str>,
+    {
+        for label in map.keys() {
+            self.label_id(label.borrow())?;
+        }
+
+        let mut labels: Vec<&str> = vec![];
+        let lmap = self
+            .labels()
+            .map(|label| {
+                let name = map.get(label).map(|n| n.as_ref()).unwrap_or(label);
+                match labels.iter().position(|l| *l == name) {
+                    Some(lid) => lid as c_int,
+                    None => {
+                        labels.push(name);
+                        labels.len() as c_int - 1
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut features: Vec<crf1dm_feature_t> = vec![];
+        let mut fids: HashMap<_, usize> = HashMap::new();
+        for mut f in self.features() {
+            if f.type_ == crfsuite_sys::FT_TRANS as c_int {
+                f.src = lmap[f.src as usize];
+            }
+            f.dst = lmap[f.dst as usize];
+            match fids.get(&(f.type_, f.src, f.dst)) {
+                Some(&fid) => features[fid].weight += f.weight,
+                None => {
+                    fids.insert((f.type_, f.src, f.dst), features.len());
+                    features.push(f);
+                }
+            }
+        }
+
+        let attrs = self.attributes().collect::<Vec<_>>();
+        write_model(&labels, &attrs, &features)
+    }
+
+    /// Compares this model with a `new` one, weight changes below `tolerance` being ignored
+    pub fn diff(&self, new: &Model, tolerance: f64) -> ModelDiff {
+        diff(self, new, tolerance)
+    }
+
+    /// Averages the weights of models sharing the same labels, typically trained with different
+    /// seeds. The attributes of all the models are kept, a missing feature having a weight of 0.
+    pub fn average(models: &[&Model]) -> Result<Vec<u8>> {
+        average(models)
+    }
+
+    /// Serializes the model with quantized weights, trading some precision for a smaller size.
+    /// Quantized models are detected and loaded transparently by `Model` and `Tagger`.
+    pub fn quantize(&self, quantization: Quantization) -> Result<Vec<u8>> {
+        quantize(&self.bytes, quantization)
+    }
+
+    /// Writes a human readable dump of the model, as the `crfsuite dump` command does
+    pub fn dump<W: Write>(&self, mut writer: W) -> Result<()> {
+        let bytes = &self.bytes;
+        let field = |offset| read_u32(bytes, offset);
+
+        writeln!(writer, "FILEHEADER = {{")?;
+        writeln!(writer, "  magic: {}", String::from_utf8_lossy(&bytes[0..4]))?;
+        writeln!(writer, "  size: {}", field(4)?)?;
+        writeln!(writer, "  type: {}", String::from_utf8_lossy(&bytes[8..12]))?;
+        writeln!(writer, "  version: {}", field(12)?)?;
+        writeln!(writer, "  num_features: {}", field(16)?)?;
+        writeln!(writer, "  num_labels: {}", field(20)?)?;
+        writeln!(writer, "  num_attrs: {}", field(24)?)?;
+        writeln!(writer, "  off_features: 0x{:X}", field(28)?)?;
+        writeln!(writer, "  off_labels: 0x{:X}", field(32)?)?;
+        writeln!(writer, "  off_attrs: 0x{:X}", field(36)?)?;
+        writeln!(writer, "  off_labelrefs: 0x{:X}", field(40)?)?;
+        writeln!(writer, "  off_attrrefs: 0x{:X}", field(44)?)?;
+        writeln!(writer, "}}\n")?;
+
+        writeln!(writer, "LABELS = {{")?;
+        for (lid, label) in self.labels().enumerate() {
+            writeln!(writer, "  {:5}: {}", lid, label)?;
+        }
+        writeln!(writer, "}}\n")?;
+
+        writeln!(writer, "ATTRIBUTES = {{")?;
+        for (aid, attr) in self.attributes().enumerate() {
+            writeln!(writer, "  {:5}: {}", aid, attr)?;
+        }
+        writeln!(writer, "}}\n")?;
+
+        writeln!(writer, "TRANSITIONS = {{")?;
+        for f in (0..self.model.num_labels()).flat_map(|lid| self.model.label_features(lid)) {
+            let (from, to) = (self.label(f.src), self.label(f.dst));
+            writeln!(
+                writer,
+                "  ({}) {} --> {}: {:.6}",
+                f.type_, from, to, f.weight
+            )?;
+        }
+        writeln!(writer, "}}\n")?;
+
+        writeln!(writer, "STATE_FEATURES = {{")?;
+        for f in (0..self.model.num_attrs()).flat_map(|aid| self.model.attr_features(aid)) {
+            let (attr, to) = (self.attr(f.src), self.label(f.dst));
+            writeln!(
+                writer,
+                "  ({}) {} --> {}: {:.6}",
+                f.type_, attr, to, f.weight
+            )?;
+        }
+        writeln!(writer, "}}\n")?;
+
+        Ok(())
+    }
+
+    /// Iterates over all the features of the model, transitions first
+    pub(crate) fn features(&self) -> impl Iterator<Item = crf1dm_feature_t> + '_ {
+        let transitions =
+            (0..self.model.num_labels()).flat_map(move |lid| self.model.label_features(lid));
+        let states = (0..self.model.num_attrs()).flat_map(move |aid| self.model.attr_features(aid));
+        transitions.chain(states)
+    }
+
+    pub(crate) fn label_id(&self, label: &str) -> Result<c_int> {
+        let c_label = CString::new(label.as_bytes())?;
+        let lid = self.model.to_lid(c_label.as_ptr());
+        if lid < 0 {
+            bail!("unknown label : {}", label)
+        }
+        Ok(lid)
+    }
+
+    /// Id of an attribute, or `None` when the attribute is unknown to the model
+    pub(crate) fn attr_id(&self, attr: &CStr) -> Option<c_int> {
+        let aid = self.model.to_aid(attr.as_ptr());
+        if aid < 0 {
+            None
+        } else {
+            Some(aid)
+        }
+    }
+
+    pub(crate) fn label(&self, lid: c_int) -> &str {
+        // all the labels were checked at creation
+        to_str(self.model.to_label(lid)).unwrap_or_default()
+    }
+
+    pub(crate) fn attr(&self, aid: c_int) -> &str {
+        // all the attributes were checked at creation
+        to_str(self.model.to_attr(aid)).unwrap_or_default()
+    }
+
+    /// Weights of the state features of an attribute as `(label id, weight)`
+    pub(crate) fn attr_weights(&self, aid: c_int) -> impl Iterator<Item = (c_int, f64)> + '_ {
+        self.model.attr_features(aid).map(|f| (f.dst, f.weight))
+    }
+}
+
+fn to_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<usize> {
+    if offset.checked_add(4).is_none_or(|end| end > bytes.len()) {
+        bail!("unexpected end of model at offset {}", offset)
+    }
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    Ok(u32::from_le_bytes(buf) as usize)
+}
+
+/// Offset of the item `index` of `size` bytes in an array starting at `base`. Offsets read from a
+/// model are not trusted, and may overflow on 32 bits targets.
+fn item_offset(base: usize, index: usize, size: usize) -> Result<usize> {
+    match index.checked_mul(size).and_then(|o| o.checked_add(base)) {
+        Some(offset) => Ok(offset),
+        None => bail!("invalid offset in model : overflow"),
+    }
+}
+
+/// Dequantizes the bytes of a model when needed and checks their layout, before they are handed
+/// over to the C code
+pub(crate) fn prepare_bytes(data: Vec<u8>) -> Result<Vec<u8>> {
+    let data = if is_quantized(&data) {
+        dequantize(&data)?
+    } else {
+        data
+    };
+
+    // the C code trusts the offsets stored in the file, make sure they are sane
+    check_layout(&data)?;
+    Ok(data)
+}
+
+fn check_layout(bytes: &[u8]) -> Result<()> {
+    if bytes.len() <= HEADER_SIZE {
+        bail!("model is too small to be a crf1dm model")
+    }
+    if &bytes[0..4] != b"lCRF" || &bytes[8..12] != b"FOMC" {
+        bail!("not a crf1dm model : wrong file magic")
+    }
+
+    let size = read_u32(bytes, 4)?;
+    if size > bytes.len() {
+        bail!(
+            "model is truncated : expected {} bytes, got {}",
+            size,
+            bytes.len()
+        )
+    }
+    let bytes = &bytes[..size];
+
+    let num_labels = read_u32(bytes, 20)?;
+    let num_attrs = read_u32(bytes, 24)?;
+    let off_features = read_u32(bytes, 28)?;
+    let off_labels = read_u32(bytes, 32)?;
+    let off_attrs = read_u32(bytes, 36)?;
+    let off_labelrefs = read_u32(bytes, 40)?;
+    let off_attrrefs = read_u32(bytes, 44)?;
+
+    if off_labels >= size || off_attrs >= size {
+        bail!("invalid dictionary offsets in model")
+    }
+
+    // the writer never fills the number of features of the file header, only the one of the
+    // feature chunk
+    let num_features = read_u32(bytes, item_offset(off_features, 2, 4)?)?;
+    let first_feature = item_offset(off_features, 1, CHUNK_SIZE)?;
+    if num_features > size / FEATURE_SIZE
+        || item_offset(first_feature, num_features, FEATURE_SIZE)? > size
+    {
+        bail!("invalid feature chunk in model")
+    }
+
+    for fid in 0..num_features {
+        // within the feature chunk, checked above
+        let offset = first_feature + FEATURE_SIZE * fid;
+        let (ty, src, dst) = (
+            read_u32(bytes, offset)?,
+            read_u32(bytes, offset + 4)?,
+            read_u32(bytes, offset + 8)?,
+        );
+        let num_src = if ty == crfsuite_sys::FT_STATE as usize {
+            num_attrs
+        } else if ty == crfsuite_sys::FT_TRANS as usize {
+            num_labels
+        } else {
+            bail!("unknown type {} for feature {}", ty, fid)
+        };
+        if src >= num_src || dst >= num_labels {
+            bail!("feature {} refers to an unknown label or attribute", fid)
+        }
+    }
+
+    for &(off_refs, num) in &[(off_labelrefs, num_labels), (off_attrrefs, num_attrs)] {
+        let first_ref = item_offset(off_refs, 1, CHUNK_SIZE)?;
+        for i in 0..num {
+            let offset = read_u32(bytes, item_offset(first_ref, i, 4)?)?;
+            let n = read_u32(bytes, offset)?;
+            for j in 1..=n {
+                if read_u32(bytes, item_offset(offset, j, 4)?)? >= num_features {
+                    bail!("invalid feature reference in model")
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Crf1dmWrapper {
+    model: *mut crfsuite_sys::crf1dm_t,
+}
+
+// see https://github.com/chokkan/crfsuite/issues/35 send should not pose any problems
+unsafe impl Send for Crf1dmWrapper {}
+
+impl Crf1dmWrapper {
+    fn num_labels(&self) -> c_int {
+        unsafe { crfsuite_sys::crf1dm_get_num_labels(self.model) }
+    }
+
+    fn num_attrs(&self) -> c_int {
+        unsafe { crfsuite_sys::crf1dm_get_num_attrs(self.model) }
+    }
+
+    fn to_label(&self, lid: c_int) -> *const c_char {
+        unsafe { crfsuite_sys::crf1dm_to_label(self.model, lid) }
+    }
+
+    fn to_lid(&self, label: *const c_char) -> c_int {
+        unsafe { crfsuite_sys::crf1dm_to_lid(self.model, label) }
+    }
+
+    fn to_aid(&self, attr: *const c_char) -> c_int {
+        unsafe { crfsuite_sys::crf1dm_to_aid(self.model, attr) }
+    }
+
+    fn to_attr(&self, aid: c_int) -> *const c_char {
+        unsafe { crfsuite_sys::crf1dm_to_attr(self.model, aid) }
+    }
+
+    fn label_features(&self, lid: c_int) -> impl Iterator<Item = crf1dm_feature_t> + '_ {
+        let mut refs: feature_refs_t = unsafe { zeroed() };
+        unsafe { crfsuite_sys::crf1dm_get_labelref(self.model, lid, &mut refs) };
+        self.features(refs)
+    }
+
+    fn attr_features(&self, aid: c_int) -> impl Iterator<Item = crf1dm_feature_t> + '_ {
+        let mut refs: feature_refs_t = unsafe { zeroed() };
+        unsafe { crfsuite_sys::crf1dm_get_attrref(self.model, aid, &mut refs) };
+        self.features(refs)
+    }
+
+    fn features(&self, mut refs: feature_refs_t) -> impl Iterator<Item = crf1dm_feature_t> + '_ {
+        (0..refs.num_features).map(move |i| {
+            let fid = unsafe { crfsuite_sys::crf1dm_get_featureid(&mut refs, i) };
+            self.feature(fid)
+        })
+    }
+
+    fn feature(&self, fid: c_int) -> crf1dm_feature_t {
+        let mut f: crf1dm_feature_t = unsafe { zeroed() };
+        unsafe { crfsuite_sys::crf1dm_get_feature(self.model, fid, &mut f) };
+        f
+    }
+}
+
+impl Drop for Crf1dmWrapper {
+    fn drop(&mut self) {
+        unsafe { crfsuite_sys::crf1dm_close(self.model) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Model;
+    use crate::tests::file_path;
+    use crate::ModelWriter;
+
+    #[test]
+    fn transitions_work() {
+        let m = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+
+        let transitions = m.transitions().collect::<Vec<_>>();
+
+        assert!(!transitions.is_empty());
+        assert!(transitions.len() <= m.num_labels() * m.num_labels());
+        for (from, to, weight) in transitions {
+            assert!(m.labels().any(|l| l == from));
+            assert!(m.labels().any(|l| l == to));
+            assert!(weight.is_finite());
+        }
+    }
+
+    #[test]
+    fn state_features_work() {
+        let m = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+
+        assert_eq!(
+            m.labels().collect::<Vec<_>>(),
+            vec!["O", "B-snips/number", "I-snips/number"]
+        );
+
+        let features = m
+            .state_features()
+            .filter(|&(attr, _, _)| attr == "built-in-snips/number:U-")
+            .collect::<Vec<_>>();
+
+        let (_, label, weight) = features
+            .iter()
+            .cloned()
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
+            .unwrap();
+        assert_eq!(label, "B-snips/number");
+        assert!(weight > 0.0);
+    }
+
+    #[test]
+    fn top_features_work() {
+        let m = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+
+        let top = m.top_features("B-snips/number", 5).unwrap();
+
+        assert_eq!(top.len(), 5);
+        for pair in top.windows(2) {
+            assert!(pair[0].1.abs() >= pair[1].1.abs());
+        }
+        assert!(m.top_features("B-snips/city", 5).is_err());
+    }
+
+    #[test]
+    fn prune_works() {
+        let m = Model::create_from_file(file_path("modela78m0U.crfsuite")).unwrap();
+
+        let pruned = Model::create_from_memory(m.prune(0.1).unwrap()).unwrap();
+
+        assert!(pruned.num_attrs() < m.num_attrs());
+        assert_eq!(
+            pruned.labels().collect::<Vec<_>>(),
+            m.labels().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            pruned.transitions().collect::<Vec<_>>(),
+            m.transitions().collect::<Vec<_>>()
+        );
+        assert!(pruned.state_features().all(|(_, _, w)| w.abs() >= 0.1));
+        assert_eq!(
+            pruned.state_features().count(),
+            m.state_features()
+                .filter(|&(_, _, w)| w.abs() >= 0.1)
+                .count()
+        );
+
+        let same = Model::create_from_memory(m.prune(0.0).unwrap()).unwrap();
+        assert_eq!(same.num_attrs(), m.num_attrs());
+        assert_eq!(
+            same.state_features().collect::<Vec<_>>(),
+            m.state_features().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rename_labels_works() {
+        let mut writer = ModelWriter::new();
+        writer.set_transition("B-snips/city", "I-snips/city", 1.0);
+        writer.set_transition("B-snips/country", "I-snips/country", 2.0);
+        writer.set_state_feature("capitalized", "B-snips/city", 0.5);
+        writer.set_state_feature("capitalized", "B-snips/country", 0.25);
+        writer.set_state_feature("in_gazetteer", "B-snips/country", 3.0);
+        let m = Model::create_from_memory(writer.write_to_bytes().unwrap()).unwrap();
+
+        let mut map = HashMap::new();
+        map.insert("B-snips/city", "B-location");
+        map.insert("B-snips/country", "B-location");
+        map.insert("I-snips/city", "I-city");
+        let renamed = Model::create_from_memory(m.rename_labels(&map).unwrap()).unwrap();
+
+        assert_eq!(
+            renamed.labels().collect::<Vec<_>>(),
+            vec!["B-location", "I-city", "I-snips/country"]
+        );
+        assert_eq!(
+            renamed.transitions().collect::<Vec<_>>(),
+            vec![
+                ("B-location", "I-city", 1.0),
+                ("B-location", "I-snips/country", 2.0)
+            ]
+        );
+        assert_eq!(
+            renamed.state_features().collect::<Vec<_>>(),
+            vec![
+                ("capitalized", "B-location", 0.75),
+                ("in_gazetteer", "B-location", 3.0)
+            ]
+        );
+
+        map.insert("B-snips/unknown", "B-unknown");
+        assert!(m.rename_labels(&map).is_err());
+    }
+
+    #[test]
+    fn invalid_model_is_rejected() {
+        assert!(Model::create_from_memory(vec![]).is_err());
+        assert!(Model::create_from_memory(vec![0; 128]).is_err());
+
+        let model = std::fs::read(file_path("modelo62R_B.crfsuite")).unwrap();
+        let mut bytes = model.clone();
+        bytes.truncate(bytes.len() / 2);
+        assert!(Model::create_from_memory(bytes).is_err());
+
+        // off_features, off_labelrefs, off_attrrefs and the first label reference, whose offsets
+        // overflow on 32 bits targets
+        let first_labelref = 0x4E0C + 12;
+        for &field in &[28, 40, 44, first_labelref] {
+            let mut bytes = model.clone();
+            bytes[field..field + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(Model::create_from_memory(bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn dump_works() {
+        let m = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let mut dump = vec![];
+        m.dump(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+
+        assert!(dump.starts_with("FILEHEADER = {\n  magic: lCRF\n  size: 22568\n"));
+        assert!(dump.contains("  off_labels: 0x1360\n"));
+        assert!(dump.contains("LABELS = {\n      0: O\n      1: B-snips/number\n"));
+        assert!(dump.contains("TRANSITIONS = {\n  (1) O --> O: 3.681807\n"));
+        assert!(dump.contains("STATE_FEATURES = {"));
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use crate::data::{ModelData, StateFeature, Transition};
+use crate::ensemble::viterbi;
+use crate::{Item, Model};
+
+/// Online training algorithms, run one sequence at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnlineAlgorithm {
+    /// Averaged perceptron, as the `ap` algorithm of crfsuite
+    AveragedPerceptron,
+    /// Passive aggressive (PA-I) with the aggressiveness parameter `c`, as the `pa` algorithm of
+    /// crfsuite
+    PassiveAggressive { c: f64 },
+}
+
+/// Trains a crf1d model online, possibly starting from the weights of an existing model
+///
+/// As crfsuite does, features are only created for the attributes and transitions seen with the
+/// reference labels, and the weights are averaged over all the updates.
+#[derive(Debug, Clone)]
+pub struct OnlineTrainer {
+    algorithm: OnlineAlgorithm,
+    labels: Vec<String>,
+    label_ids: HashMap<String, usize>,
+    attributes: Vec<String>,
+    attr_ids: HashMap<String, usize>,
+    // feature ids of the labels of each attribute
+    attr_features: Vec<Vec<(usize, usize)>>,
+    // feature ids of the (from, to) label transitions
+    transition_features: HashMap<(usize, usize), usize>,
+    weights: Vec<f64>,
+    // updates weighted by the step at which they were made, to average the weights
+    weighted_updates: Vec<f64>,
+    step: usize,
+}
+
+impl OnlineTrainer {
+    pub fn new(algorithm: OnlineAlgorithm) -> OnlineTrainer {
+        OnlineTrainer {
+            algorithm,
+            labels: vec![],
+            label_ids: HashMap::new(),
+            attributes: vec![],
+            attr_ids: HashMap::new(),
+            attr_features: vec![],
+            transition_features: HashMap::new(),
+            weights: vec![],
+            weighted_updates: vec![],
+            step: 1,
+        }
+    }
+
+    /// Starts from the labels, attributes and weights of a trained model
+    pub fn from_model(model: &Model, algorithm: OnlineAlgorithm) -> OnlineTrainer {
+        let mut trainer = OnlineTrainer::new(algorithm);
+        for label in model.labels() {
+            trainer.label_id(label);
+        }
+        for (from, to, weight) in model.transitions() {
+            let from = trainer.label_id(from);
+            let to = trainer.label_id(to);
+            let fid = trainer.transition_feature(from, to);
+            trainer.weights[fid] = weight;
+        }
+        for (attribute, label, weight) in model.state_features() {
+            let aid = trainer.attr_id(attribute);
+            let lid = trainer.label_id(label);
+            let fid = trainer.state_feature(aid, lid);
+            trainer.weights[fid] = weight;
+        }
+        trainer
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn num_features(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Updates the weights with a labeled sequence, returning the number of items whose labels
+    /// were mispredicted before the update
+    pub fn update<I: Item, S: AsRef<str>>(&mut self, items: &[I], labels: &[S]) -> Result<usize> {
+        if items.len() != labels.len() {
+            bail!(
+                "The numbers of items and labels differ: |x| = {}, |y| = {}",
+                items.len(),
+                labels.len()
+            )
+        }
+
+        let mut attributes = Vec::with_capacity(items.len());
+        let mut buffer = vec![];
+        for item in items {
+            let mut item_attributes = vec![];
+            item.for_each_attribute(|attribute| {
+                buffer.clear();
+                attribute.write_attr(&mut buffer);
+                let name = String::from_utf8_lossy(&buffer);
+                item_attributes.push((self.attr_id(&name), attribute.get_value()));
+                Ok(())
+            })?;
+            attributes.push(item_attributes);
+        }
+        let reference = labels
+            .iter()
+            .map(|l| self.label_id(l.as_ref()))
+            .collect::<Vec<_>>();
+
+        // the features of the reference labels are the ones the model can learn
+        for (item_attributes, &lid) in attributes.iter().zip(&reference) {
+            for &(aid, _) in item_attributes {
+                self.state_feature(aid, lid);
+            }
+        }
+        for pair in reference.windows(2) {
+            self.transition_feature(pair[0], pair[1]);
+        }
+
+        let prediction = self.predict(&self.weights, &attributes);
+        let num_errors = reference
+            .iter()
+            .zip(&prediction)
+            .filter(|(r, p)| r != p)
+            .count();
+
+        if num_errors > 0 {
+            // difference between the feature vectors of the reference and of the prediction
+            let mut delta = HashMap::new();
+            for (sign, path) in &[(1.0, &reference), (-1.0, &prediction)] {
+                self.for_each_feature(&attributes, path, |fid, value| {
+                    *delta.entry(fid).or_insert(0.0) += sign * value;
+                });
+            }
+            delta.retain(|_, d: &mut f64| *d != 0.0);
+
+            let rate = match self.algorithm {
+                OnlineAlgorithm::AveragedPerceptron => 1.0,
+                OnlineAlgorithm::PassiveAggressive { c } => {
+                    let margin = delta
+                        .iter()
+                        .map(|(&fid, d)| self.weights[fid] * d)
+                        .sum::<f64>();
+                    let loss = (num_errors as f64).sqrt() - margin;
+                    let norm = delta.values().map(|d| d * d).sum::<f64>();
+                    if loss <= 0.0 || norm == 0.0 {
+                        0.0
+                    } else {
+                        (loss / norm).min(c)
+                    }
+                }
+            };
+            for (fid, d) in delta {
+                self.weights[fid] += rate * d;
+                self.weighted_updates[fid] += self.step as f64 * rate * d;
+            }
+        }
+
+        self.step += 1;
+        Ok(num_errors)
+    }
+
+    /// Labels of a sequence according to the averaged weights
+    pub fn tag<I: Item>(&self, items: &[I]) -> Result<Vec<String>> {
+        let mut attributes = Vec::with_capacity(items.len());
+        let mut buffer = vec![];
+        for item in items {
+            let mut item_attributes = vec![];
+            item.for_each_attribute(|attribute| {
+                buffer.clear();
+                attribute.write_attr(&mut buffer);
+                if let Some(&aid) = self.attr_ids.get(&*String::from_utf8_lossy(&buffer)) {
+                    item_attributes.push((aid, attribute.get_value()));
+                }
+                Ok(())
+            })?;
+            attributes.push(item_attributes);
+        }
+        Ok(self
+            .predict(&self.averaged_weights(), &attributes)
+            .into_iter()
+            .map(|lid| self.labels[lid].clone())
+            .collect())
+    }
+
+    /// Model with the averaged weights, features with a null weight being left out
+    pub fn to_model_data(&self) -> ModelData {
+        let weights = self.averaged_weights();
+        let mut transitions = self
+            .transition_features
+            .iter()
+            .filter(|(_, &fid)| weights[fid] != 0.0)
+            .map(|(&(from, to), &fid)| Transition {
+                from: self.labels[from].clone(),
+                to: self.labels[to].clone(),
+                weight: weights[fid],
+            })
+            .collect::<Vec<_>>();
+        transitions.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+
+        let state_features = self
+            .attr_features
+            .iter()
+            .enumerate()
+            .flat_map(|(aid, features)| {
+                features
+                    .iter()
+                    .filter(|(_, fid)| weights[*fid] != 0.0)
+                    .map(move |&(lid, fid)| (aid, lid, fid))
+            })
+            .map(|(aid, lid, fid)| StateFeature {
+                attribute: self.attributes[aid].clone(),
+                label: self.labels[lid].clone(),
+                weight: weights[fid],
+            })
+            .collect();
+
+        ModelData {
+            labels: self.labels.clone(),
+            attributes: self.attributes.clone(),
+            transitions,
+            state_features,
+        }
+    }
+
+    /// Writes the model with the averaged weights
+    pub fn write_to_bytes(&self) -> Result<Vec<u8>> {
+        self.to_model_data().to_bytes()
+    }
+
+    fn averaged_weights(&self) -> Vec<f64> {
+        self.weights
+            .iter()
+            .zip(&self.weighted_updates)
+            .map(|(w, u)| w - u / self.step as f64)
+            .collect()
+    }
+
+    fn predict(&self, weights: &[f64], attributes: &[Vec<(usize, f64)>]) -> Vec<usize> {
+        let num_labels = self.labels.len();
+        let states = attributes
+            .iter()
+            .map(|item_attributes| {
+                let mut scores = vec![0.0; num_labels];
+                for &(aid, value) in item_attributes {
+                    for &(lid, fid) in &self.attr_features[aid] {
+                        scores[lid] += value * weights[fid];
+                    }
+                }
+                scores
+            })
+            .collect::<Vec<_>>();
+
+        let mut transitions = vec![0.0; num_labels * num_labels];
+        for (&(from, to), &fid) in &self.transition_features {
+            transitions[from * num_labels + to] = weights[fid];
+        }
+        viterbi(&transitions, &states)
+    }
+
+    /// Calls `f` with the id and value of each existing feature fired by a label path
+    fn for_each_feature<F: FnMut(usize, f64)>(
+        &self,
+        attributes: &[Vec<(usize, f64)>],
+        path: &[usize],
+        mut f: F,
+    ) {
+        for (item_attributes, &lid) in attributes.iter().zip(path) {
+            for &(aid, value) in item_attributes {
+                if let Some(&(_, fid)) = self.attr_features[aid].iter().find(|(l, _)| *l == lid) {
+                    f(fid, value);
+                }
+            }
+        }
+        for pair in path.windows(2) {
+            if let Some(&fid) = self.transition_features.get(&(pair[0], pair[1])) {
+                f(fid, 1.0);
+            }
+        }
+    }
+
+    fn label_id(&mut self, label: &str) -> usize {
+        if let Some(&lid) = self.label_ids.get(label) {
+            return lid;
+        }
+        self.labels.push(label.to_string());
+        self.label_ids
+            .insert(label.to_string(), self.labels.len() - 1);
+        self.labels.len() - 1
+    }
+
+    fn attr_id(&mut self, attribute: &str) -> usize {
+        if let Some(&aid) = self.attr_ids.get(attribute) {
+            return aid;
+        }
+        self.attributes.push(attribute.to_string());
+        self.attr_features.push(vec![]);
+        self.attr_ids
+            .insert(attribute.to_string(), self.attributes.len() - 1);
+        self.attributes.len() - 1
+    }
+
+    fn state_feature(&mut self, aid: usize, lid: usize) -> usize {
+        if let Some(&(_, fid)) = self.attr_features[aid].iter().find(|(l, _)| *l == lid) {
+            return fid;
+        }
+        let fid = self.new_feature();
+        self.attr_features[aid].push((lid, fid));
+        fid
+    }
+
+    fn transition_feature(&mut self, from: usize, to: usize) -> usize {
+        if let Some(&fid) = self.transition_features.get(&(from, to)) {
+            return fid;
+        }
+        let fid = self.new_feature();
+        self.transition_features.insert((from, to), fid);
+        fid
+    }
+
+    fn new_feature(&mut self) -> usize {
+        self.weights.push(0.0);
+        self.weighted_updates.push(0.0);
+        self.weights.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnlineAlgorithm, OnlineTrainer};
+    use crate::tests::file_path;
+    use crate::{Model, Tagger};
+
+    fn data() -> Vec<(Vec<Vec<&'static str>>, Vec<&'static str>)> {
+        vec![
+            (
+                vec![vec!["w:go", "first"], vec!["w:paris"], vec!["w:now"]],
+                vec!["O", "B-city", "O"],
+            ),
+            (
+                vec![vec!["w:in", "first"], vec!["w:new"], vec!["w:york"]],
+                vec!["O", "B-city", "I-city"],
+            ),
+        ]
+    }
+
+    #[test]
+    fn online_training_works() {
+        for &algorithm in &[
+            OnlineAlgorithm::AveragedPerceptron,
+            OnlineAlgorithm::PassiveAggressive { c: 1.0 },
+        ] {
+            let mut trainer = OnlineTrainer::new(algorithm);
+            for _ in 0..10 {
+                for (items, labels) in data() {
+                    trainer.update(&items, &labels).unwrap();
+                }
+            }
+            for (items, labels) in data() {
+                assert_eq!(trainer.update(&items, &labels).unwrap(), 0);
+            }
+
+            let tagger = Tagger::create_from_memory(trainer.write_to_bytes().unwrap()).unwrap();
+            for (items, labels) in data() {
+                assert_eq!(tagger.tag(&items).unwrap(), labels);
+            }
+            assert!(trainer.update(&[vec!["w:a"]], &["O", "O"]).is_err());
+        }
+    }
+
+    #[test]
+    fn warm_start_works() {
+        let model = Model::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        let mut trainer = OnlineTrainer::from_model(&model, OnlineAlgorithm::AveragedPerceptron);
+        assert_eq!(trainer.labels().len(), model.num_labels());
+        let num_features = trainer.num_features();
+
+        // without updates, the weights are the ones of the model
+        let data = trainer.to_model_data();
+        assert_eq!(
+            data.state_features.len(),
+            model.state_features().filter(|f| f.2 != 0.0).count()
+        );
+        let items = vec![
+            vec![
+                "is_first:1",
+                "ngram_1:me",
+                "ngram_1[+1]:two",
+                "shape_ngram_1:xxx",
+            ],
+            vec!["ngram_1:two", "built-in-snips/number:B-", "ngram_1[-1]:me"],
+            vec!["built-in-snips/number:L-", "ngram_1[-1]:two", "is_last:1"],
+        ];
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        assert_eq!(trainer.tag(&items).unwrap(), tagger.tag(&items).unwrap());
+
+        // a user correction
+        let corrected = vec!["O", "O", "O"];
+        for _ in 0..20 {
+            trainer.update(&items, &corrected).unwrap();
+        }
+        assert_eq!(trainer.tag(&items).unwrap(), corrected);
+        // the features of the model are kept
+        assert!(trainer.num_features() >= num_features);
+    }
+}
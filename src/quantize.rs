@@ -0,0 +1,305 @@
+use half::f16;
+
+use anyhow::{bail, Result};
+
+const FEATURE_SIZE: usize = 20;
+const FEATURE_IDS_SIZE: usize = 12;
+const CHUNK_SIZE: usize = 12;
+const VERSION_OFFSET: usize = 12;
+const VERSION_INT8: u32 = 101;
+const VERSION_FLOAT16: u32 = 102;
+
+/// Storage of the feature weights in a quantized model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantization {
+    /// Weights are stored on one byte, scaled by the largest absolute weight of the model
+    Int8,
+    /// Weights are stored as half precision floats, scaled by the largest absolute weight of the
+    /// model to fit their range
+    Float16,
+}
+
+impl Quantization {
+    fn version(self) -> u32 {
+        match self {
+            Quantization::Int8 => VERSION_INT8,
+            Quantization::Float16 => VERSION_FLOAT16,
+        }
+    }
+
+    fn weight_size(self) -> usize {
+        match self {
+            Quantization::Int8 => 1,
+            Quantization::Float16 => 2,
+        }
+    }
+
+    fn from_version(version: u32) -> Option<Quantization> {
+        match version {
+            VERSION_INT8 => Some(Quantization::Int8),
+            VERSION_FLOAT16 => Some(Quantization::Float16),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn is_quantized(bytes: &[u8]) -> bool {
+    quantization(bytes).is_some()
+}
+
+pub(crate) fn quantization(bytes: &[u8]) -> Option<Quantization> {
+    if bytes.len() < VERSION_OFFSET + 4 || &bytes[0..4] != b"lCRF" {
+        return None;
+    }
+    read_u32(bytes, VERSION_OFFSET)
+        .ok()
+        .and_then(Quantization::from_version)
+}
+
+/// Rewrites the feature chunk of a valid crf1dm model with quantized weights. The quantized
+/// feature chunk is laid out as the original one, followed by the scale of the weights, and
+/// each feature only stores its quantized weight after its type, source and destination.
+pub(crate) fn quantize(bytes: &[u8], quantization: Quantization) -> Result<Vec<u8>> {
+    let off_features = read_u32(bytes, 28)? as usize;
+    let num_features = read_u32(bytes, add(off_features, 8)?)? as usize;
+    let end = off_features + CHUNK_SIZE + FEATURE_SIZE * num_features;
+
+    let features = &bytes[off_features + CHUNK_SIZE..end];
+    let weights = features
+        .chunks(FEATURE_SIZE)
+        .map(|f| read_f64(f, FEATURE_IDS_SIZE))
+        .collect::<Result<Vec<_>>>()?;
+
+    let max = weights.iter().fold(0.0f64, |max, w| max.max(w.abs()));
+    let scale = if max > 0.0 {
+        match quantization {
+            Quantization::Int8 => max / f64::from(i8::MAX),
+            Quantization::Float16 => max / f16::MAX.to_f64(),
+        }
+    } else {
+        1.0
+    };
+
+    let mut chunk = chunk_header(bytes, off_features, num_features)?;
+    chunk.extend_from_slice(&scale.to_le_bytes());
+    for (f, w) in features.chunks(FEATURE_SIZE).zip(weights) {
+        chunk.extend_from_slice(&f[..FEATURE_IDS_SIZE]);
+        match quantization {
+            Quantization::Int8 => chunk.push((w / scale).round() as i8 as u8),
+            Quantization::Float16 => {
+                chunk.extend_from_slice(&f16::from_f64(w / scale).to_bits().to_le_bytes())
+            }
+        }
+    }
+    // keep the following chunks aligned on a DWORD boundary
+    let padding = (4 - chunk.len() % 4) % 4;
+    chunk.resize(chunk.len() + padding, 0);
+
+    let mut quantized = replace_features(bytes, end, chunk)?;
+    quantized[VERSION_OFFSET..VERSION_OFFSET + 4]
+        .copy_from_slice(&quantization.version().to_le_bytes());
+
+    Ok(quantized)
+}
+
+/// Restores a crf1dm model readable by the C code from a quantized one
+pub(crate) fn dequantize(bytes: &[u8]) -> Result<Vec<u8>> {
+    let quantization = match Quantization::from_version(read_u32(bytes, VERSION_OFFSET)?) {
+        Some(quantization) => quantization,
+        None => bail!("not a quantized model"),
+    };
+    let quantized_size = FEATURE_IDS_SIZE + quantization.weight_size();
+
+    let off_features = read_u32(bytes, 28)? as usize;
+    let num_features = read_u32(bytes, add(off_features, 8)?)? as usize;
+    let scale = read_f64(bytes, add(off_features, CHUNK_SIZE)?)?;
+    // the scale was read, so this is within the model
+    let start = off_features + CHUNK_SIZE + 8;
+    if num_features > bytes.len() / quantized_size {
+        bail!("invalid feature chunk in quantized model")
+    }
+    let mut end = start + quantized_size * num_features;
+    end += (4 - (end - off_features) % 4) % 4;
+    if end > bytes.len() {
+        bail!("invalid feature chunk in quantized model")
+    }
+
+    let mut chunk = chunk_header(bytes, off_features, num_features)?;
+    for f in bytes[start..start + quantized_size * num_features].chunks(quantized_size) {
+        let w = match quantization {
+            Quantization::Int8 => f64::from(f[FEATURE_IDS_SIZE] as i8),
+            Quantization::Float16 => {
+                let mut buf = [0; 2];
+                buf.copy_from_slice(&f[FEATURE_IDS_SIZE..]);
+                f16::from_bits(u16::from_le_bytes(buf)).to_f64()
+            }
+        };
+        chunk.extend_from_slice(&f[..FEATURE_IDS_SIZE]);
+        chunk.extend_from_slice(&(w * scale).to_le_bytes());
+    }
+
+    let mut model = replace_features(bytes, end, chunk)?;
+    model[VERSION_OFFSET..VERSION_OFFSET + 4].copy_from_slice(&100u32.to_le_bytes());
+
+    Ok(model)
+}
+
+fn chunk_header(bytes: &[u8], off_features: usize, num_features: usize) -> Result<Vec<u8>> {
+    if add(off_features, CHUNK_SIZE)? > bytes.len() {
+        bail!("invalid feature chunk in model")
+    }
+    // the size is filled once the chunk is complete
+    let mut chunk = bytes[off_features..off_features + 8].to_vec();
+    chunk.extend_from_slice(&(num_features as u32).to_le_bytes());
+    Ok(chunk)
+}
+
+/// Swaps the feature chunk ending at `end` by `chunk`, moving all the offsets pointing after it
+fn replace_features(bytes: &[u8], end: usize, mut chunk: Vec<u8>) -> Result<Vec<u8>> {
+    let off_features = read_u32(bytes, 28)? as usize;
+    if end > bytes.len() {
+        bail!("invalid feature chunk in model")
+    }
+    let chunk_size = chunk.len() as u32;
+    chunk[4..8].copy_from_slice(&chunk_size.to_le_bytes());
+
+    let mut out = Vec::with_capacity(bytes.len() + chunk.len());
+    out.extend_from_slice(&bytes[..off_features]);
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&bytes[end..]);
+
+    let new_end = off_features + chunk.len();
+    let relocate = |offset: u32| {
+        if offset as usize >= end {
+            (offset as usize - end + new_end) as u32
+        } else {
+            offset
+        }
+    };
+
+    // size, off_labels, off_attrs, off_labelrefs and off_attrrefs
+    for &field in &[4, 32, 36, 40, 44] {
+        let offset = relocate(read_u32(&out, field)?);
+        out[field..field + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    // the feature references store absolute offsets
+    for &field in &[40, 44] {
+        let off_refs = read_u32(&out, field)? as usize;
+        let num = read_u32(&out, add(off_refs, 8)?)? as usize;
+        let first_ref = add(off_refs, CHUNK_SIZE)?;
+        for i in 0..num {
+            // reading fails past the end of the model, long before this could overflow
+            let position = first_ref + 4 * i;
+            let offset = read_u32(&out, position)?;
+            if offset != 0 {
+                out[position..position + 4].copy_from_slice(&relocate(offset).to_le_bytes());
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Adds to an offset read from a model, which may overflow on 32 bits targets
+fn add(offset: usize, delta: usize) -> Result<usize> {
+    match offset.checked_add(delta) {
+        Some(offset) => Ok(offset),
+        None => bail!("invalid offset in model : overflow"),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    if add(offset, 4)? > bytes.len() {
+        bail!("unexpected end of model at offset {}", offset)
+    }
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f64(bytes: &[u8], offset: usize) -> Result<f64> {
+    if add(offset, 8)? > bytes.len() {
+        bail!("unexpected end of model at offset {}", offset)
+    }
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[offset..offset + 8]);
+    Ok(f64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quantization;
+    use crate::tests::file_path;
+    use crate::{Model, ModelWriter, Tagger};
+
+    #[test]
+    fn quantized_models_work() {
+        let m = Model::create_from_file(file_path("modela78m0U.crfsuite")).unwrap();
+        let original_size = std::fs::metadata(file_path("modela78m0U.crfsuite"))
+            .unwrap()
+            .len() as usize;
+
+        let max = m
+            .state_features()
+            .chain(m.transitions())
+            .fold(0.0f64, |max, (_, _, w)| max.max(w.abs()));
+
+        let input = vec![
+            vec![
+                ("is_first".to_string(), "1".to_string()),
+                ("ngram_1".to_string(), "set".to_string()),
+            ],
+            vec![("ngram_1".to_string(), "rare_word".to_string())],
+            vec![
+                ("ngram_1".to_string(), "to".to_string()),
+                ("is_last".to_string(), "1".to_string()),
+            ],
+        ];
+        let expected = Tagger::create_from_file(file_path("modela78m0U.crfsuite"))
+            .unwrap()
+            .tag(&input)
+            .unwrap();
+
+        for &(quantization, tolerance) in &[
+            (Quantization::Int8, max / 127.0),
+            (Quantization::Float16, max / 1024.0),
+        ] {
+            let bytes = m.quantize(quantization).unwrap();
+            assert!(bytes.len() < original_size);
+
+            let q = Model::create_from_memory(bytes.clone()).unwrap();
+            assert_eq!(
+                q.attributes().collect::<Vec<_>>(),
+                m.attributes().collect::<Vec<_>>()
+            );
+            for (a, b) in q.state_features().zip(m.state_features()) {
+                assert_eq!((a.0, a.1), (b.0, b.1));
+                assert!((a.2 - b.2).abs() <= tolerance);
+            }
+
+            let t = Tagger::create_from_memory(bytes).unwrap();
+            assert_eq!(t.tag(&input).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn float16_keeps_the_range_of_weights() {
+        let mut writer = ModelWriter::new();
+        writer.set_state_feature("large", "A", 1e5);
+        writer.set_state_feature("small", "A", -1e-3);
+        writer.set_transition("A", "B", 2.5);
+        let m = Model::create_from_memory(writer.write_to_bytes().unwrap()).unwrap();
+
+        let q = Model::create_from_memory(m.quantize(Quantization::Float16).unwrap()).unwrap();
+        let weights = |m: &Model| {
+            m.state_features()
+                .chain(m.transitions())
+                .map(|(_, _, w)| w)
+                .collect::<Vec<_>>()
+        };
+        for (a, b) in weights(&q).into_iter().zip(weights(&m)) {
+            assert!((a - b).abs() <= b.abs() / 1024.0);
+        }
+    }
+}
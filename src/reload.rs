@@ -0,0 +1,261 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+
+use crate::Tagger;
+
+type Validation = Box<dyn Fn(&Tagger) -> Result<()> + Send + Sync>;
+
+/// Model which can be replaced while it is used from several threads
+///
+/// Taggers are checked out of a pool belonging to the model they were built from, so that the
+/// requests in flight during a reload finish on the previous model. New bytes are validated
+/// before being swapped in, the current model being kept when they are rejected.
+pub struct ReloadableModel {
+    current: RwLock<Arc<Loaded>>,
+    validation: Option<Validation>,
+    path: Option<PathBuf>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+struct Loaded {
+    bytes: Vec<u8>,
+    version: u64,
+    taggers: Mutex<Vec<Tagger>>,
+}
+
+/// Tagger checked out of a `ReloadableModel`, going back to its pool when dropped
+pub struct PooledTagger {
+    tagger: Option<Tagger>,
+    model: Arc<Loaded>,
+}
+
+impl ReloadableModel {
+    pub fn new(bytes: Vec<u8>) -> Result<ReloadableModel> {
+        let tagger = Tagger::create_from_memory(bytes.clone())?;
+        Ok(ReloadableModel {
+            current: RwLock::new(Arc::new(Loaded {
+                bytes,
+                version: 0,
+                taggers: Mutex::new(vec![tagger]),
+            })),
+            validation: None,
+            path: None,
+            modified: Mutex::new(None),
+        })
+    }
+
+    /// Loads a model from a file, which `reload_if_modified` watches
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ReloadableModel> {
+        let path = path.as_ref();
+        let modified = fs::metadata(path)?.modified().ok();
+        let mut model = ReloadableModel::new(fs::read(path)?)?;
+        model.path = Some(path.to_path_buf());
+        model.modified = Mutex::new(modified);
+        Ok(model)
+    }
+
+    /// Checks run on a tagger of the new model before each reload, a failure rejecting the model
+    pub fn set_validation<F>(&mut self, validation: F)
+    where
+        F: Fn(&Tagger) -> Result<()> + Send + Sync + 'static,
+    {
+        self.validation = Some(Box::new(validation));
+    }
+
+    /// Number of successful reloads
+    pub fn version(&self) -> u64 {
+        self.current().version
+    }
+
+    /// Bytes of the current model
+    pub fn bytes(&self) -> Vec<u8> {
+        self.current().bytes.clone()
+    }
+
+    /// Checks a tagger out of the pool of the current model, building one when none is free
+    pub fn tagger(&self) -> Result<PooledTagger> {
+        let model = self.current();
+        let tagger = model.taggers.lock().unwrap().pop();
+        let tagger = match tagger {
+            Some(tagger) => tagger,
+            None => Tagger::create_from_memory(model.bytes.clone())?,
+        };
+        Ok(PooledTagger {
+            tagger: Some(tagger),
+            model,
+        })
+    }
+
+    /// Validates the new model and swaps it in, keeping the current one on failure
+    pub fn reload(&self, bytes: Vec<u8>) -> Result<()> {
+        let tagger = Tagger::create_from_memory(bytes.clone())?;
+        if let Some(validation) = &self.validation {
+            validation(&tagger)?;
+        }
+
+        let mut current = self.current.write().unwrap();
+        *current = Arc::new(Loaded {
+            bytes,
+            version: current.version + 1,
+            taggers: Mutex::new(vec![tagger]),
+        });
+        Ok(())
+    }
+
+    /// Reloads the model from its file when it has been modified since the last load
+    ///
+    /// A rejected file is not tried again until it is modified anew.
+    pub fn reload_if_modified(&self) -> Result<bool> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => bail!("the model was not loaded from a file"),
+        };
+        let modified = fs::metadata(path)?.modified().ok();
+        {
+            let mut last = self.modified.lock().unwrap();
+            if modified.is_some() && *last == modified {
+                return Ok(false);
+            }
+            *last = modified;
+        }
+        self.reload(fs::read(path)?)?;
+        Ok(true)
+    }
+
+    fn current(&self) -> Arc<Loaded> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+}
+
+impl PooledTagger {
+    /// Version of the model of the tagger, as given by `ReloadableModel::version`
+    pub fn version(&self) -> u64 {
+        self.model.version
+    }
+}
+
+impl Deref for PooledTagger {
+    type Target = Tagger;
+
+    fn deref(&self) -> &Tagger {
+        self.tagger.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledTagger {
+    fn drop(&mut self) {
+        if let Some(tagger) = self.tagger.take() {
+            if let Ok(mut taggers) = self.model.taggers.lock() {
+                taggers.push(tagger);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReloadableModel;
+    use crate::tests::file_path;
+    use crate::ModelWriter;
+    use anyhow::bail;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn read(name: &str) -> Vec<u8> {
+        fs::read(file_path(name)).unwrap()
+    }
+
+    #[test]
+    fn reload_works() {
+        let model = ReloadableModel::new(read("modela78m0U.crfsuite")).unwrap();
+        let old = model.tagger().unwrap();
+        let old_labels = old.labels().unwrap();
+
+        model.reload(read("modelo62R_B.crfsuite")).unwrap();
+        assert_eq!(model.version(), 1);
+        assert_eq!(model.bytes(), read("modelo62R_B.crfsuite"));
+
+        // the tagger in flight still uses the old model
+        assert_eq!(old.version(), 0);
+        assert_eq!(old.labels().unwrap(), old_labels);
+
+        let new = model.tagger().unwrap();
+        assert_eq!(new.version(), 1);
+        assert!(new
+            .labels()
+            .unwrap()
+            .contains(&"B-snips/number".to_string()));
+    }
+
+    #[test]
+    fn reload_rolls_back() {
+        let mut model = ReloadableModel::new(read("modela78m0U.crfsuite")).unwrap();
+        assert!(model.reload(b"not a model".to_vec()).is_err());
+
+        model.set_validation(|tagger| {
+            if !tagger.labels()?.contains(&"O".to_string()) {
+                bail!("missing outside label")
+            }
+            Ok(())
+        });
+        model.reload(read("modelo62R_B.crfsuite")).unwrap();
+
+        // a model without the outside label
+        let mut writer = ModelWriter::new();
+        writer.add_label("B-city");
+        assert!(model.reload(writer.write_to_bytes().unwrap()).is_err());
+
+        assert_eq!(model.version(), 1);
+        assert_eq!(model.bytes(), read("modelo62R_B.crfsuite"));
+    }
+
+    #[test]
+    fn taggers_are_pooled_across_threads() {
+        let model = Arc::new(ReloadableModel::new(read("modelo62R_B.crfsuite")).unwrap());
+        let handles = (0..4)
+            .map(|i| {
+                let model = Arc::clone(&model);
+                thread::spawn(move || {
+                    if i == 2 {
+                        model.reload(read("modela78m0U.crfsuite")).unwrap();
+                    }
+                    let tagger = model.tagger().unwrap();
+                    tagger.tag(&[vec!["is_first:1"]]).unwrap().len()
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 1);
+        }
+        assert_eq!(model.version(), 1);
+    }
+
+    #[test]
+    fn reload_if_modified_works() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.crfsuite");
+        fs::write(&path, read("modela78m0U.crfsuite")).unwrap();
+
+        let model = ReloadableModel::from_file(&path).unwrap();
+        assert!(!model.reload_if_modified().unwrap());
+
+        // make sure the modification time changes on coarse file systems
+        thread::sleep(Duration::from_millis(1100));
+        fs::write(&path, read("modelo62R_B.crfsuite")).unwrap();
+        assert!(model.reload_if_modified().unwrap());
+        assert_eq!(model.version(), 1);
+        assert!(!model.reload_if_modified().unwrap());
+
+        assert!(ReloadableModel::new(read("modela78m0U.crfsuite"))
+            .unwrap()
+            .reload_if_modified()
+            .is_err());
+    }
+}
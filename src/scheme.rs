@@ -0,0 +1,245 @@
+/// Tagging scheme of the entities spanning several tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaggingScheme {
+    /// `B-` begins an entity, `I-` continues it
+    Bio,
+    /// As BIO, with `E-` ending an entity and `S-` for single token entities
+    Bioes,
+    /// As BIO, with `L-` ending an entity and `U-` for single token entities
+    Bilou,
+}
+
+impl TaggingScheme {
+    /// Prefix of the token at `position` of an entity spanning `len` tokens
+    pub(crate) fn prefix(self, position: usize, len: usize) -> &'static str {
+        match (self, position, len) {
+            (TaggingScheme::Bioes, _, 1) => "S-",
+            (TaggingScheme::Bilou, _, 1) => "U-",
+            (_, 0, _) => "B-",
+            (TaggingScheme::Bioes, p, l) if p + 1 == l => "E-",
+            (TaggingScheme::Bilou, p, l) if p + 1 == l => "L-",
+            _ => "I-",
+        }
+    }
+
+    /// Prefixes of the labels, the outside label excluded
+    fn prefixes(self) -> &'static [char] {
+        match self {
+            TaggingScheme::Bio => &['B', 'I'],
+            TaggingScheme::Bioes => &['B', 'I', 'E', 'S'],
+            TaggingScheme::Bilou => &['B', 'I', 'L', 'U'],
+        }
+    }
+}
+
+/// How the labels of a tagging scheme are written, `B-city` by default
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelFormat {
+    pub scheme: TaggingScheme,
+    /// Separator between the prefix and the entity
+    pub separator: String,
+    /// Whether the prefix follows the entity, as in `city-B`
+    pub suffix: bool,
+}
+
+impl LabelFormat {
+    pub fn new(scheme: TaggingScheme) -> LabelFormat {
+        LabelFormat {
+            scheme,
+            separator: "-".to_string(),
+            suffix: false,
+        }
+    }
+
+    /// Entities of a label sequence, decoded leniently as [`spans`] does
+    ///
+    /// Labels whose prefix is not part of the scheme are outside of any entity.
+    pub fn spans<S: AsRef<str>>(&self, labels: &[S]) -> Vec<Span> {
+        decode(labels, |label| self.split_label(label))
+    }
+
+    fn split_label<'a>(&self, label: &'a str) -> (Option<char>, &'a str) {
+        let split = if self.suffix {
+            label
+                .rsplit_once(self.separator.as_str())
+                .map(|(entity, prefix)| (prefix, entity))
+        } else {
+            label.split_once(self.separator.as_str())
+        };
+        match split {
+            Some((prefix, entity)) => {
+                let mut chars = prefix.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(p), None) if self.scheme.prefixes().contains(&p) => (Some(p), entity),
+                    _ => (None, label),
+                }
+            }
+            None => (None, label),
+        }
+    }
+}
+
+/// Entity spanning the tokens from `start` to `end`, excluded
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub entity: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn overlaps(&self, other: &Span) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// Entities of a label sequence tagged with BIO, BIOES or BILOU
+///
+/// As conlleval does, ill-formed sequences are decoded leniently: an `I-` label following
+/// an `O` label or a label of another entity starts a new entity.
+pub fn spans<S: AsRef<str>>(labels: &[S]) -> Vec<Span> {
+    decode(labels, split_label)
+}
+
+fn decode<'a, S, F>(labels: &'a [S], split_label: F) -> Vec<Span>
+where
+    S: AsRef<str>,
+    F: Fn(&'a str) -> (Option<char>, &'a str),
+{
+    let mut spans = vec![];
+    let mut current: Option<Span> = None;
+
+    for (t, label) in labels.iter().enumerate() {
+        let (prefix, entity) = split_label(label.as_ref());
+
+        let continues = match (&current, prefix) {
+            (Some(span), Some('I')) | (Some(span), Some('E')) | (Some(span), Some('L')) => {
+                span.entity == entity
+            }
+            _ => false,
+        };
+        if !continues {
+            spans.extend(current.take());
+            if prefix.is_some() {
+                current = Some(Span {
+                    entity: entity.to_string(),
+                    start: t,
+                    end: t,
+                });
+            }
+        }
+        if let Some(span) = current.as_mut() {
+            span.end = t + 1;
+        }
+        // these prefixes close the entity
+        if let Some('E') | Some('L') | Some('S') | Some('U') = prefix {
+            spans.extend(current.take());
+        }
+    }
+    spans.extend(current);
+    spans
+}
+
+/// Entity predicted by a tagger, with the marginal probability of its labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedSpan {
+    pub label: String,
+    pub start: usize,
+    pub end: usize,
+    pub probability: f64,
+}
+
+/// Prefix and entity of a label, labels without a known prefix being outside of any entity
+fn split_label(label: &str) -> (Option<char>, &str) {
+    let mut chars = label.chars();
+    match (chars.next(), chars.next()) {
+        (Some(p), Some('-')) if "BIESLU".contains(p) => (Some(p), &label[2..]),
+        _ => (None, label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spans, LabelFormat, Span, TaggingScheme};
+
+    fn span(entity: &str, start: usize, end: usize) -> Span {
+        Span {
+            entity: entity.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn spans_work() {
+        assert_eq!(
+            spans(&["B-city", "I-city", "O", "B-city", "B-country", "I-country"]),
+            vec![
+                span("city", 0, 2),
+                span("city", 3, 4),
+                span("country", 4, 6)
+            ]
+        );
+        assert_eq!(
+            spans(&["U-number", "B-number", "L-number", "S-city", "B-city", "E-city"]),
+            vec![
+                span("number", 0, 1),
+                span("number", 1, 3),
+                span("city", 3, 4),
+                span("city", 4, 6)
+            ]
+        );
+        // ill-formed sequences
+        assert_eq!(
+            spans(&["I-city", "I-country", "O", "I-city", "L-city", "I-city"]),
+            vec![
+                span("city", 0, 1),
+                span("country", 1, 2),
+                span("city", 3, 5),
+                span("city", 5, 6)
+            ]
+        );
+        assert!(spans::<&str>(&[]).is_empty());
+        assert!(spans(&["O", "O"]).is_empty());
+    }
+
+    #[test]
+    fn label_format_works() {
+        let bio = LabelFormat::new(TaggingScheme::Bio);
+        assert_eq!(
+            bio.spans(&["B-city", "I-city", "B-a-b", "U-city", "O"]),
+            vec![span("city", 0, 2), span("a-b", 2, 3)]
+        );
+
+        let bilou = LabelFormat {
+            scheme: TaggingScheme::Bilou,
+            separator: "_".to_string(),
+            suffix: true,
+        };
+        assert_eq!(
+            bilou.spans(&["city_U", "my_city_B", "my_city_L", "city-U", "O"]),
+            vec![span("city", 0, 1), span("my_city", 1, 3)]
+        );
+    }
+
+    #[test]
+    fn prefix_works() {
+        let prefixes = |scheme: TaggingScheme, len| {
+            (0..len).map(|p| scheme.prefix(p, len)).collect::<Vec<_>>()
+        };
+        assert_eq!(prefixes(TaggingScheme::Bio, 3), vec!["B-", "I-", "I-"]);
+        assert_eq!(prefixes(TaggingScheme::Bio, 1), vec!["B-"]);
+        assert_eq!(prefixes(TaggingScheme::Bioes, 3), vec!["B-", "I-", "E-"]);
+        assert_eq!(prefixes(TaggingScheme::Bioes, 1), vec!["S-"]);
+        assert_eq!(prefixes(TaggingScheme::Bilou, 2), vec!["B-", "L-"]);
+        assert_eq!(prefixes(TaggingScheme::Bilou, 1), vec!["U-"]);
+    }
+}
@@ -0,0 +1,292 @@
+//! Reader and writer for the CRFsuite text data format, used by the `crfsuite` command for both
+//! training and tagging.
+//!
+//! Each line holds an item: its label followed by its attributes, separated by tabs. Attributes
+//! are written `name:value`, or `name` when the value is 1, colons and backslashes in names being
+//! escaped with a backslash. Sequences are separated by blank lines, and may start with a
+//! `@weight:<value>` line.
+
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Result};
+
+use crate::SimpleAttribute;
+
+/// A sequence of items along with their labels, which are empty for unlabeled data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    pub labels: Vec<String>,
+    pub items: Vec<Vec<SimpleAttribute>>,
+    /// Weight of the sequence during training
+    pub weight: f64,
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence {
+            labels: vec![],
+            items: vec![],
+            weight: 1.0,
+        }
+    }
+}
+
+impl Sequence {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+/// Iterates over the sequences of a CRFsuite data file
+pub struct Reader<R: BufRead> {
+    reader: R,
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R) -> Reader<R> {
+        Reader {
+            reader,
+            line: String::new(),
+            line_number: 0,
+        }
+    }
+
+    fn read_sequence(&mut self) -> Result<Option<Sequence>> {
+        let mut sequence = Sequence::default();
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                break;
+            }
+            self.line_number += 1;
+
+            let line = self.line.trim_end_matches(&['\n', '\r'][..]);
+            if line.is_empty() {
+                if sequence.is_empty() {
+                    continue;
+                }
+                break;
+            }
+
+            let mut fields = line.split('\t');
+            let (label, value) = parse_field(fields.next().unwrap_or_default(), self.line_number)?;
+            if label == "@weight" {
+                if !sequence.is_empty() {
+                    bail!(
+                        "line {} : weight declared in the middle of a sequence",
+                        self.line_number
+                    )
+                }
+                sequence.weight = value;
+                continue;
+            }
+
+            let mut item = vec![];
+            for field in fields.filter(|f| !f.is_empty()) {
+                let (attr, value) = parse_field(field, self.line_number)?;
+                item.push(SimpleAttribute { attr, value });
+            }
+            sequence.labels.push(label);
+            sequence.items.push(item);
+        }
+
+        if sequence.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(sequence))
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Sequence>;
+
+    fn next(&mut self) -> Option<Result<Sequence>> {
+        self.read_sequence().transpose()
+    }
+}
+
+/// Reads all the sequences of a CRFsuite data file
+pub fn read_sequences<R: BufRead>(reader: R) -> Result<Vec<Sequence>> {
+    Reader::new(reader).collect()
+}
+
+/// Writes sequences in the CRFsuite data format, items without labels starting with an empty
+/// field
+pub fn write_sequences<W: Write>(mut writer: W, sequences: &[Sequence]) -> Result<()> {
+    for sequence in sequences {
+        if !sequence.labels.is_empty() && sequence.labels.len() != sequence.items.len() {
+            bail!(
+                "sequence has {} labels for {} items",
+                sequence.labels.len(),
+                sequence.items.len()
+            )
+        }
+        if sequence.weight != 1.0 {
+            writeln!(writer, "@weight:{}", sequence.weight)?;
+        }
+        for (i, item) in sequence.items.iter().enumerate() {
+            let label = sequence.labels.get(i).map(|l| l.as_str()).unwrap_or("");
+            write!(writer, "{}", escape(label)?)?;
+            for attribute in item {
+                write!(writer, "\t{}", escape(&attribute.attr)?)?;
+                if attribute.value != 1.0 {
+                    write!(writer, ":{}", attribute.value)?;
+                }
+            }
+            writeln!(writer)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Splits a field on its first unescaped colon
+fn parse_field(field: &str, line_number: usize) -> Result<(String, f64)> {
+    let mut name = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => name.push(escaped),
+                None => bail!("line {} : unterminated escape in {:?}", line_number, field),
+            },
+            ':' => {
+                let value = chars.as_str();
+                return match value.parse() {
+                    Ok(value) => Ok((name, value)),
+                    Err(_) => bail!("line {} : invalid value in {:?}", line_number, field),
+                };
+            }
+            c => name.push(c),
+        }
+    }
+    Ok((name, 1.0))
+}
+
+fn escape(name: &str) -> Result<String> {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '\\' | ':' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\t' | '\n' | '\r' => bail!("{:?} cannot be written in a CRFsuite data file", name),
+            c => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_sequences, write_sequences, Sequence};
+    use crate::tests::file_path;
+    use crate::{SimpleAttribute, Tagger};
+
+    fn attribute(attr: &str, value: f64) -> SimpleAttribute {
+        SimpleAttribute {
+            attr: attr.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn read_sequences_works() {
+        let data = "@weight:2.5\n\
+                    O\tis_first\\:1\tngram_1\\:set:0.5\n\
+                    B-snips/number\tbuilt-in-snips/number\\:U-\tis_last\\:1\r\n\
+                    \n\
+                    \n\
+                    \tpath\\\\to\n";
+
+        let sequences = read_sequences(data.as_bytes()).unwrap();
+
+        assert_eq!(
+            sequences,
+            vec![
+                Sequence {
+                    labels: vec!["O".to_string(), "B-snips/number".to_string()],
+                    items: vec![
+                        vec![attribute("is_first:1", 1.0), attribute("ngram_1:set", 0.5)],
+                        vec![
+                            attribute("built-in-snips/number:U-", 1.0),
+                            attribute("is_last:1", 1.0)
+                        ],
+                    ],
+                    weight: 2.5,
+                },
+                Sequence {
+                    labels: vec!["".to_string()],
+                    items: vec![vec![attribute("path\\to", 1.0)]],
+                    weight: 1.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        assert!(read_sequences("O\tngram_1:set\n".as_bytes()).is_err());
+        assert!(read_sequences("O\tngram_1\\".as_bytes()).is_err());
+        assert!(read_sequences("O\ta\n@weight:2\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn write_sequences_round_trip_works() {
+        let sequences = vec![
+            Sequence {
+                labels: vec!["O".to_string(), "B-snips/number".to_string()],
+                items: vec![
+                    vec![attribute("is_first:1", 1.0), attribute("a\\b", -0.25)],
+                    vec![attribute("is_last:1", 3.0)],
+                ],
+                weight: 0.5,
+            },
+            Sequence {
+                labels: vec![],
+                items: vec![vec![attribute("ngram_1:four", 1.0)]],
+                weight: 1.0,
+            },
+        ];
+
+        let mut buffer = vec![];
+        write_sequences(&mut buffer, &sequences).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "@weight:0.5\n\
+             O\tis_first\\:1\ta\\\\b:-0.25\n\
+             B-snips/number\tis_last\\:1:3\n\
+             \n\
+             \tngram_1\\:four\n\
+             \n"
+        );
+
+        let read = read_sequences(buffer.as_slice()).unwrap();
+        assert_eq!(read[0], sequences[0]);
+        assert_eq!(read[1].labels, vec![""]);
+        assert_eq!(read[1].items, sequences[1].items);
+    }
+
+    #[test]
+    fn read_sequences_feed_tagger() {
+        let data = "\tis_first\\:1\tngram_1\\:me\tngram_1[+1]\\:two\tshape_ngram_1\\:xxx\n\
+                    \tngram_1\\:two\tbuilt-in-snips/number\\:B-\tngram_1[-1]\\:me\n\
+                    \tbuilt-in-snips/number\\:L-\tngram_1[-1]\\:two\tis_last\\:1\n";
+        let sequences = read_sequences(data.as_bytes()).unwrap();
+
+        let tagger = Tagger::create_from_file(file_path("modelo62R_B.crfsuite")).unwrap();
+        assert_eq!(
+            tagger.tag(&sequences[0].items).unwrap(),
+            vec!["O", "B-snips/number", "I-snips/number"]
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use std::ffi::{CStr, CString};
+use std::io::Write;
+use std::mem::zeroed;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::ptr::null_mut;
+use std::slice;
+use std::str::FromStr;
+
+use anyhow::{bail, format_err, Result};
+
+use crate::{attr_to_c_str, FeatureHashing, Item};
+
+/// Training algorithms of crfsuite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Gradient descent using the L-BFGS method
+    Lbfgs,
+    /// Stochastic gradient descent with L2 regularization
+    L2sgd,
+    /// Averaged perceptron
+    AveragedPerceptron,
+    /// Passive aggressive
+    PassiveAggressive,
+    /// Adaptive regularization of weights
+    Arow,
+}
+
+impl Algorithm {
+    /// Name of the algorithm, as given to the `-a` option of the `crfsuite` command
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Lbfgs => "lbfgs",
+            Algorithm::L2sgd => "l2sgd",
+            Algorithm::AveragedPerceptron => "ap",
+            Algorithm::PassiveAggressive => "pa",
+            Algorithm::Arow => "arow",
+        }
+    }
+
+    /// Name of the algorithm in the identifier of the trainer instance
+    fn interface(self) -> &'static str {
+        match self {
+            Algorithm::AveragedPerceptron => "averaged-perceptron",
+            Algorithm::PassiveAggressive => "passive-aggressive",
+            _ => self.name(),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Algorithm> {
+        Ok(match s {
+            "lbfgs" => Algorithm::Lbfgs,
+            "l2sgd" => Algorithm::L2sgd,
+            "ap" | "averaged-perceptron" => Algorithm::AveragedPerceptron,
+            "pa" | "passive-aggressive" => Algorithm::PassiveAggressive,
+            "arow" => Algorithm::Arow,
+            _ => bail!("unknown training algorithm {}", s),
+        })
+    }
+}
+
+/// Trains crf1d models on labeled sequences, as the `crfsuite learn` command does
+pub struct Trainer {
+    trainer: *mut crfsuite_sys::crfsuite_trainer_t,
+    data: crfsuite_sys::crfsuite_data_t,
+    hashing: Option<FeatureHashing>,
+}
+
+// the trainer owns its data and its dictionaries
+unsafe impl Send for Trainer {}
+
+impl Trainer {
+    pub fn new(algorithm: Algorithm) -> Result<Trainer> {
+        let mut trainer = null_mut();
+        let tid = CString::new(format!("train/crf1d/{}", algorithm.interface()))?;
+        let r = unsafe { crfsuite_sys::crfsuite_create_instance(tid.as_ptr(), &mut trainer) };
+        if r == 0 || trainer.is_null() {
+            bail!("error while creating trainer : failed to create a C instance...")
+        }
+
+        let mut data = unsafe { zeroed() };
+        unsafe { crfsuite_sys::crfsuite_data_init(&mut data) };
+
+        let mut trainer = Trainer {
+            trainer: trainer as *mut _,
+            data,
+            hashing: None,
+        };
+        trainer.data.attrs = create_dictionary()?;
+        trainer.data.labels = create_dictionary()?;
+        Ok(trainer)
+    }
+
+    /// Prints the progress of the training on the standard output
+    pub fn set_verbose(&mut self, verbose: bool) {
+        let callback = if verbose {
+            unsafe { crfsuite_sys::crfsuite_rs_stdout_logging }
+        } else {
+            None
+        };
+        unsafe {
+            if let Some(set_message_callback) = (*self.trainer).set_message_callback {
+                set_message_callback(self.trainer, null_mut(), callback)
+            } else {
+                panic!("no callback for set_message_callback")
+            }
+        }
+    }
+
+    /// Hashes the attributes of the sequences appended afterwards, the model being then used by
+    /// taggers with the same hashing
+    pub fn set_feature_hashing(&mut self, hashing: Option<FeatureHashing>) {
+        self.hashing = hashing;
+    }
+
+    /// Names of the parameters of the training algorithm
+    pub fn params(&self) -> Vec<String> {
+        let params = self.parameters();
+        (0..params.num())
+            .filter_map(|i| {
+                let mut name = null_mut();
+                if params.name(i, &mut name) != 0 {
+                    return None;
+                }
+                let s = unsafe { CStr::from_ptr(name) }
+                    .to_string_lossy()
+                    .into_owned();
+                params.free(name);
+                Some(s)
+            })
+            .collect()
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let c_name = CString::new(name)?;
+        let c_value = CString::new(value)?;
+        if self.parameters().set(c_name.as_ptr(), c_value.as_ptr()) != 0 {
+            bail!("parameter not found: {} = {}", name, value)
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<String> {
+        let params = self.parameters();
+        let c_name = CString::new(name)?;
+        let mut value = null_mut();
+        if params.get(c_name.as_ptr(), &mut value) != 0 {
+            bail!("parameter not found: {}", name)
+        }
+        let s = unsafe { CStr::from_ptr(value) }
+            .to_string_lossy()
+            .into_owned();
+        params.free(value);
+        Ok(s)
+    }
+
+    /// Type and description of a parameter
+    pub fn help(&self, name: &str) -> Result<(String, String)> {
+        let params = self.parameters();
+        let c_name = CString::new(name)?;
+        let mut ty = null_mut();
+        let mut help = null_mut();
+        if params.help(c_name.as_ptr(), &mut ty, &mut help) != 0 {
+            bail!("parameter not found: {}", name)
+        }
+        let result = (
+            unsafe { CStr::from_ptr(ty) }.to_string_lossy().into_owned(),
+            unsafe { CStr::from_ptr(help) }
+                .to_string_lossy()
+                .into_owned(),
+        );
+        params.free(ty);
+        params.free(help);
+        Ok(result)
+    }
+
+    /// Adds a labeled sequence to the training data, the sequences of the `holdout` group given
+    /// to `train` being used for evaluation only
+    pub fn append<I: Item, S: AsRef<str>>(
+        &mut self,
+        items: &[I],
+        labels: &[S],
+        weight: f64,
+        group: usize,
+    ) -> Result<()> {
+        if items.len() != labels.len() {
+            bail!(
+                "The numbers of items and labels differ: |x| = {}, |y| = {}",
+                items.len(),
+                labels.len()
+            )
+        }
+
+        // resolve everything first so that a failure does not append a partial instance
+        let mut buffer = vec![];
+        let mut attributes = Vec::with_capacity(items.len());
+        for item in items {
+            let mut item_attributes = vec![];
+            item.for_each_attribute(|attribute| {
+                buffer.clear();
+                attribute.write_attr(&mut buffer);
+                if let Some(hashing) = self.hashing {
+                    let bucket = hashing.bucket(&buffer);
+                    buffer.clear();
+                    write!(buffer, "#{}", bucket)?;
+                }
+                let aid = dictionary_get(self.data.attrs, attr_to_c_str(&mut buffer)?.as_ptr());
+                item_attributes.push((aid, attribute.get_value()));
+                Ok(())
+            })?;
+            attributes.push(item_attributes);
+        }
+        let lids = labels
+            .iter()
+            .map(|l| {
+                let label = CString::new(l.as_ref())?;
+                Ok(dictionary_get(self.data.labels, label.as_ptr()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut inst = unsafe { zeroed() };
+        unsafe { crfsuite_sys::crfsuite_instance_init_n(&mut inst, items.len() as c_int) };
+        inst.weight = weight;
+        inst.group = group as c_int;
+
+        let inst_items = unsafe { slice::from_raw_parts_mut(inst.items, inst.num_items as usize) };
+        let inst_labels =
+            unsafe { slice::from_raw_parts_mut(inst.labels, inst.num_items as usize) };
+        for ((inst_item, item_attributes), (inst_label, &lid)) in inst_items
+            .iter_mut()
+            .zip(&attributes)
+            .zip(inst_labels.iter_mut().zip(&lids))
+        {
+            unsafe { crfsuite_sys::crfsuite_item_init(inst_item) };
+            for &(aid, value) in item_attributes {
+                let cont = &mut unsafe { zeroed() };
+                unsafe { crfsuite_sys::crfsuite_attribute_set(cont, aid, value) };
+                unsafe { crfsuite_sys::crfsuite_item_append_attribute(inst_item, cont) };
+            }
+            *inst_label = lid;
+        }
+
+        unsafe {
+            crfsuite_sys::crfsuite_data_append(&mut self.data, &inst);
+            crfsuite_sys::crfsuite_instance_finish(&mut inst);
+        }
+        Ok(())
+    }
+
+    pub fn num_instances(&self) -> usize {
+        self.data.num_instances as usize
+    }
+
+    /// Groups of the training data, numbered from 0
+    pub fn num_groups(&self) -> usize {
+        self.instances()
+            .iter()
+            .map(|inst| inst.group as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sets the group of each sequence, in the order they were appended
+    pub fn set_groups(&mut self, groups: &[usize]) -> Result<()> {
+        if groups.len() != self.num_instances() {
+            bail!(
+                "{} groups given for {} instances",
+                groups.len(),
+                self.num_instances()
+            )
+        }
+        for (inst, &group) in self.instances_mut().iter_mut().zip(groups) {
+            inst.group = group as c_int;
+        }
+        Ok(())
+    }
+
+    /// Removes the training data
+    pub fn clear(&mut self) -> Result<()> {
+        // crfsuite_data_finish resets the dictionaries of the data without releasing them
+        let (attrs, labels) = (self.data.attrs, self.data.labels);
+        unsafe { crfsuite_sys::crfsuite_data_finish(&mut self.data) };
+        release_dictionary(attrs);
+        release_dictionary(labels);
+        self.data.attrs = create_dictionary()?;
+        self.data.labels = create_dictionary()?;
+        Ok(())
+    }
+
+    /// Trains a model on the data, writing it to `model` when given
+    ///
+    /// The sequences of the `holdout` group are left out of the training and used to evaluate the
+    /// model after each iteration.
+    pub fn train<P: AsRef<Path>>(
+        &mut self,
+        model: Option<P>,
+        holdout: Option<usize>,
+    ) -> Result<()> {
+        if self.data.num_instances == 0 {
+            bail!("The data is empty. Call Trainer::append before Trainer::train.")
+        }
+        let model = match model {
+            Some(path) => {
+                let path = path.as_ref();
+                CString::new(
+                    path.to_str()
+                        .ok_or_else(|| format_err!("invalid model path {:?}", path))?,
+                )?
+            }
+            None => CString::default(),
+        };
+        let holdout = holdout.map(|h| h as c_int).unwrap_or(-1);
+
+        let r = unsafe {
+            if let Some(train) = (*self.trainer).train {
+                train(self.trainer, &self.data, model.as_ptr(), holdout)
+            } else {
+                panic!("no callback for train")
+            }
+        };
+        if r != 0 {
+            bail!("error while training : non zero C return code {}...", r)
+        }
+        Ok(())
+    }
+
+    fn instances(&self) -> &[crfsuite_sys::crfsuite_instance_t] {
+        if self.data.instances.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.data.instances, self.data.num_instances as usize) }
+    }
+
+    fn instances_mut(&mut self) -> &mut [crfsuite_sys::crfsuite_instance_t] {
+        if self.data.instances.is_null() {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.data.instances, self.data.num_instances as usize) }
+    }
+
+    fn parameters(&self) -> ParamsWrapper {
+        let params = unsafe {
+            if let Some(params) = (*self.trainer).params {
+                params(self.trainer)
+            } else {
+                panic!("no callback for params")
+            }
+        };
+        ParamsWrapper { params }
+    }
+}
+
+impl Drop for Trainer {
+    fn drop(&mut self) {
+        let (attrs, labels) = (self.data.attrs, self.data.labels);
+        unsafe {
+            crfsuite_sys::crfsuite_data_finish(&mut self.data);
+            if let Some(release) = (*self.trainer).release {
+                release(self.trainer);
+            } else {
+                panic!("no callback for release")
+            }
+        }
+        release_dictionary(attrs);
+        release_dictionary(labels);
+    }
+}
+
+fn create_dictionary() -> Result<*mut crfsuite_sys::crfsuite_dictionary_t> {
+    let mut dict = null_mut();
+    let iid = CString::new("dictionary")?;
+    let r = unsafe { crfsuite_sys::crfsuite_create_instance(iid.as_ptr(), &mut dict) };
+    if r == 0 || dict.is_null() {
+        bail!("failed to create a dictionary instance")
+    }
+    Ok(dict as *mut _)
+}
+
+/// Identifier of a string, added to the dictionary when missing
+fn dictionary_get(dict: *mut crfsuite_sys::crfsuite_dictionary_t, str: *const c_char) -> c_int {
+    unsafe {
+        if let Some(get) = (*dict).get {
+            get(dict, str)
+        } else {
+            panic!("no callback for get")
+        }
+    }
+}
+
+fn release_dictionary(dict: *mut crfsuite_sys::crfsuite_dictionary_t) {
+    if dict.is_null() {
+        return;
+    }
+    unsafe {
+        if let Some(release) = (*dict).release {
+            release(dict);
+        } else {
+            panic!("no callback for release")
+        }
+    }
+}
+
+struct ParamsWrapper {
+    params: *mut crfsuite_sys::crfsuite_params_t,
+}
+
+impl ParamsWrapper {
+    fn num(&self) -> c_int {
+        unsafe {
+            if let Some(num) = (*self.params).num {
+                num(self.params)
+            } else {
+                panic!("no callback for num")
+            }
+        }
+    }
+
+    fn name(&self, i: c_int, ptr_name: *mut *mut c_char) -> c_int {
+        unsafe {
+            if let Some(name) = (*self.params).name {
+                name(self.params, i, ptr_name)
+            } else {
+                panic!("no callback for name")
+            }
+        }
+    }
+
+    fn set(&self, name: *const c_char, value: *const c_char) -> c_int {
+        unsafe {
+            if let Some(set) = (*self.params).set {
+                set(self.params, name, value)
+            } else {
+                panic!("no callback for set")
+            }
+        }
+    }
+
+    fn get(&self, name: *const c_char, ptr_value: *mut *mut c_char) -> c_int {
+        unsafe {
+            if let Some(get) = (*self.params).get {
+                get(self.params, name, ptr_value)
+            } else {
+                panic!("no callback for get")
+            }
+        }
+    }
+
+    fn help(
+        &self,
+        name: *const c_char,
+        ptr_type: *mut *mut c_char,
+        ptr_help: *mut *mut c_char,
+    ) -> c_int {
+        unsafe {
+            if let Some(help) = (*self.params).help {
+                help(self.params, name, ptr_type, ptr_help)
+            } else {
+                panic!("no callback for help")
+            }
+        }
+    }
+
+    fn free(&self, str: *const c_char) {
+        unsafe {
+            if let Some(free) = (*self.params).free {
+                free(self.params, str)
+            } else {
+                panic!("no callback for free")
+            }
+        }
+    }
+}
+
+impl Drop for ParamsWrapper {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(release) = (*self.params).release {
+                release(self.params);
+            } else {
+                panic!("no callback for release")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Algorithm, Trainer};
+    use crate::{FeatureHashing, Tagger};
+    use tempfile::tempdir;
+
+    fn trainer(algorithm: Algorithm) -> Trainer {
+        let mut trainer = Trainer::new(algorithm).unwrap();
+        for _ in 0..5 {
+            trainer
+                .append(
+                    &[vec!["w:go", "first"], vec!["w:paris"], vec!["w:now"]],
+                    &["O", "B-city", "O"],
+                    1.0,
+                    0,
+                )
+                .unwrap();
+            trainer
+                .append(
+                    &[vec!["w:in", "first"], vec!["w:new"], vec!["w:york"]],
+                    &["O", "B-city", "I-city"],
+                    1.0,
+                    1,
+                )
+                .unwrap();
+        }
+        trainer
+    }
+
+    #[test]
+    fn trainer_works() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("model.crfsuite");
+
+        let mut trainer = trainer(Algorithm::Lbfgs);
+        assert_eq!(trainer.num_instances(), 10);
+        assert_eq!(trainer.num_groups(), 2);
+        trainer.set("max_iterations", "50").unwrap();
+        trainer.train(Some(&path), None).unwrap();
+
+        let tagger = Tagger::create_from_file(&path).unwrap();
+        assert_eq!(
+            tagger
+                .tag(&[vec!["w:go", "first"], vec!["w:paris"]])
+                .unwrap(),
+            vec!["O", "B-city"]
+        );
+        assert_eq!(
+            tagger.tag(&[vec!["w:new"], vec!["w:york"]]).unwrap(),
+            vec!["B-city", "I-city"]
+        );
+    }
+
+    #[test]
+    fn trainer_params_work() {
+        let mut trainer = Trainer::new(Algorithm::AveragedPerceptron).unwrap();
+        assert!(trainer.params().contains(&"max_iterations".to_string()));
+        trainer.set("max_iterations", "10").unwrap();
+        assert_eq!(trainer.get("max_iterations").unwrap(), "10");
+        assert_eq!(trainer.help("max_iterations").unwrap().0, "int");
+        assert!(trainer.set("unknown", "1").is_err());
+        assert!(trainer.get("unknown").is_err());
+        assert!("unknown".parse::<Algorithm>().is_err());
+        assert_eq!(
+            "pa".parse::<Algorithm>().unwrap(),
+            Algorithm::PassiveAggressive
+        );
+    }
+
+    #[test]
+    fn trainer_holdout_works() {
+        let mut trainer = trainer(Algorithm::Arow);
+        assert!(trainer.train(None::<&str>, Some(1)).is_ok());
+
+        assert!(trainer.append(&[vec!["w:a"]], &["O", "O"], 1.0, 0).is_err());
+        assert!(trainer.set_groups(&[0, 1]).is_err());
+        trainer.set_groups(&[0; 10]).unwrap();
+        assert_eq!(trainer.num_groups(), 1);
+
+        trainer.clear().unwrap();
+        assert_eq!(trainer.num_instances(), 0);
+        assert!(trainer.train(None::<&str>, None).is_err());
+        trainer.append(&[vec!["w:a"]], &["O"], 1.0, 0).unwrap();
+        assert_eq!(trainer.num_instances(), 1);
+    }
+
+    #[test]
+    fn trainer_with_hashing_works() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("model.crfsuite");
+        let hashing = FeatureHashing::new(1 << 20).unwrap();
+
+        let mut trainer = Trainer::new(Algorithm::Lbfgs).unwrap();
+        trainer.set_feature_hashing(Some(hashing));
+        for _ in 0..5 {
+            trainer
+                .append(&[vec!["w:go"], vec!["w:paris"]], &["O", "B-city"], 1.0, 0)
+                .unwrap();
+        }
+        trainer.train(Some(&path), None).unwrap();
+
+        let mut tagger = Tagger::create_from_file(&path).unwrap();
+        let attributes = tagger.model().unwrap().attributes().collect::<Vec<_>>();
+        assert!(attributes.contains(&hashing.hashed_name(b"w:paris").as_str()));
+        assert!(!attributes.contains(&"w:paris"));
+
+        tagger.set_feature_hashing(Some(hashing));
+        assert_eq!(
+            tagger.tag(&[vec!["w:go"], vec!["w:paris"]]).unwrap(),
+            vec!["O", "B-city"]
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::os::raw::{c_int, c_void};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use crfsuite_sys::crf1dm_feature_t;
+
+use crate::model::{CHUNK_SIZE, FEATURE_SIZE, HEADER_SIZE};
+
+const VERSION_NUMBER: u32 = 100;
+const CQDB_NUM_TABLES: usize = 256;
+const CQDB_BYTEORDER_CHECK: u32 = 0x6244_5371;
+// a 24 bytes header followed by the (offset, size) of each hash table
+const CQDB_OFFSET_DATA: usize = 24 + 8 * CQDB_NUM_TABLES;
+
+/// Builds a crf1dm model, in the format loaded by `Tagger` and `Model`, from labels, attributes
+/// and feature weights
+///
+/// Labels and attributes are registered the first time they are used, and get their ids in this
+/// order.
+#[derive(Debug, Default, Clone)]
+pub struct ModelWriter {
+    labels: Vec<String>,
+    attrs: Vec<String>,
+    label_ids: HashMap<String, c_int>,
+    attr_ids: HashMap<String, c_int>,
+    features: Vec<crf1dm_feature_t>,
+    // position of each feature in `features`, by (type, source, destination)
+    feature_ids: HashMap<(c_int, c_int, c_int), usize>,
+}
+
+impl ModelWriter {
+    pub fn new() -> ModelWriter {
+        ModelWriter::default()
+    }
+
+    /// Registers a label and returns its id. Labels without any feature are kept in the model.
+    pub fn add_label(&mut self, label: &str) -> usize {
+        intern(&mut self.labels, &mut self.label_ids, label) as usize
+    }
+
+    /// Registers an attribute and returns its id
+    pub fn add_attribute(&mut self, attribute: &str) -> usize {
+        intern(&mut self.attrs, &mut self.attr_ids, attribute) as usize
+    }
+
+    /// Sets the weight of the transition from label `from` to label `to`, replacing any
+    /// previous weight
+    pub fn set_transition(&mut self, from: &str, to: &str, weight: f64) {
+        let src = intern(&mut self.labels, &mut self.label_ids, from);
+        let dst = intern(&mut self.labels, &mut self.label_ids, to);
+        self.set_feature(crfsuite_sys::FT_TRANS as c_int, src, dst, weight);
+    }
+
+    /// Sets the weight of the state feature linking `attribute` to `label`, replacing any
+    /// previous weight
+    pub fn set_state_feature(&mut self, attribute: &str, label: &str, weight: f64) {
+        let src = intern(&mut self.attrs, &mut self.attr_ids, attribute);
+        let dst = intern(&mut self.labels, &mut self.label_ids, label);
+        self.set_feature(crfsuite_sys::FT_STATE as c_int, src, dst, weight);
+    }
+
+    pub fn num_labels(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn num_attrs(&self) -> usize {
+        self.attrs.len()
+    }
+
+    pub fn num_features(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let labels = self.labels.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+        let attrs = self.attrs.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+        fs::write(path, write_model(&labels, &attrs, &self.features)?)?;
+        Ok(())
+    }
+
+    pub fn write_to_bytes(&self) -> Result<Vec<u8>> {
+        let labels = self.labels.iter().map(|l| l.as_str()).collect::<Vec<_>>();
+        let attrs = self.attrs.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+        write_model(&labels, &attrs, &self.features)
+    }
+
+    fn set_feature(&mut self, type_: c_int, src: c_int, dst: c_int, weight: f64) {
+        match self.feature_ids.get(&(type_, src, dst)) {
+            Some(&fid) => self.features[fid].weight = weight,
+            None => {
+                self.feature_ids
+                    .insert((type_, src, dst), self.features.len());
+                self.features.push(crf1dm_feature_t {
+                    type_,
+                    src,
+                    dst,
+                    weight,
+                });
+            }
+        }
+    }
+}
+
+fn intern(values: &mut Vec<String>, ids: &mut HashMap<String, c_int>, value: &str) -> c_int {
+    if let Some(&id) = ids.get(value) {
+        return id;
+    }
+    let id = values.len() as c_int;
+    values.push(value.to_string());
+    ids.insert(value.to_string(), id);
+    id
+}
+
+/// Writes a crf1dm model and returns its bytes. The ids of the features refer to the positions
+/// in `labels` and `attrs`.
+///
+/// The layout is the one written by the crf1dmw writer of crfsuite, byte for byte.
+pub(crate) fn write_model(
+    labels: &[&str],
+    attrs: &[&str],
+    features: &[crf1dm_feature_t],
+) -> Result<Vec<u8>> {
+    let mut label_refs = vec![vec![]; labels.len()];
+    let mut attr_refs = vec![vec![]; attrs.len()];
+
+    for (fid, f) in features.iter().enumerate() {
+        let refs = if f.type_ == crfsuite_sys::FT_STATE as c_int {
+            &mut attr_refs
+        } else if f.type_ == crfsuite_sys::FT_TRANS as c_int {
+            &mut label_refs
+        } else {
+            bail!("unknown type {} for feature {}", f.type_, fid)
+        };
+        if f.src < 0 || f.src as usize >= refs.len() || f.dst < 0 || f.dst as usize >= labels.len()
+        {
+            bail!("feature {} refers to an unknown label or attribute", fid)
+        }
+        refs[f.src as usize].push(fid as u32);
+    }
+
+    // the header is filled once all the chunks are written
+    let mut bytes = vec![0; HEADER_SIZE];
+
+    let off_features = bytes.len();
+    put_bytes(&mut bytes, b"FEAT");
+    put_u32(
+        &mut bytes,
+        (CHUNK_SIZE + FEATURE_SIZE * features.len()) as u32,
+    );
+    put_u32(&mut bytes, features.len() as u32);
+    for f in features {
+        put_u32(&mut bytes, f.type_ as u32);
+        put_u32(&mut bytes, f.src as u32);
+        put_u32(&mut bytes, f.dst as u32);
+        put_bytes(&mut bytes, &f.weight.to_le_bytes());
+    }
+
+    let off_labels = bytes.len();
+    write_cqdb(&mut bytes, labels)?;
+    let off_attrs = bytes.len();
+    write_cqdb(&mut bytes, attrs)?;
+
+    // the trainer reserves two extra slots for the BOS and EOS labels
+    let off_labelrefs = write_refs(&mut bytes, b"LFRF", &label_refs, labels.len() + 2);
+    let off_attrrefs = write_refs(&mut bytes, b"AFRF", &attr_refs, attrs.len());
+
+    let size = bytes.len() as u32;
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    put_bytes(&mut header, b"lCRF");
+    put_u32(&mut header, size);
+    put_bytes(&mut header, b"FOMC");
+    put_u32(&mut header, VERSION_NUMBER);
+    // crfsuite never fills the number of features of the header, the feature chunk has it
+    put_u32(&mut header, 0);
+    put_u32(&mut header, labels.len() as u32);
+    put_u32(&mut header, attrs.len() as u32);
+    for offset in &[
+        off_features,
+        off_labels,
+        off_attrs,
+        off_labelrefs,
+        off_attrrefs,
+    ] {
+        put_u32(&mut header, *offset as u32);
+    }
+    bytes[..HEADER_SIZE].copy_from_slice(&header);
+
+    Ok(bytes)
+}
+
+/// Writes a chunk of feature references, `num` being the number of slots of the offset array,
+/// and returns the offset of the chunk
+fn write_refs(bytes: &mut Vec<u8>, chunk: &[u8], refs: &[Vec<u32>], num: usize) -> usize {
+    // chunks are aligned on 4 bytes
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(0);
+    }
+
+    let begin = bytes.len();
+    // the offset array is filled once the references are written, unused slots stay at 0
+    bytes.resize(begin + CHUNK_SIZE + 4 * num, 0);
+    let mut offsets = Vec::with_capacity(num);
+    for fids in refs {
+        offsets.push(bytes.len() as u32);
+        put_u32(bytes, fids.len() as u32);
+        for &fid in fids {
+            put_u32(bytes, fid);
+        }
+    }
+    let size = (bytes.len() - begin) as u32;
+
+    let mut header = Vec::with_capacity(CHUNK_SIZE + 4 * offsets.len());
+    put_bytes(&mut header, chunk);
+    put_u32(&mut header, size);
+    put_u32(&mut header, num as u32);
+    for offset in offsets {
+        put_u32(&mut header, offset);
+    }
+    bytes[begin..begin + header.len()].copy_from_slice(&header);
+
+    begin
+}
+
+/// Writes a CQDB database mapping `values` to their position, as the cqdb writer of crfsuite
+/// does
+fn write_cqdb(bytes: &mut Vec<u8>, values: &[&str]) -> Result<()> {
+    let begin = bytes.len();
+    bytes.resize(begin + CQDB_OFFSET_DATA, 0);
+
+    // (hash, offset) of the records of each table, and offset of each record by id
+    let mut tables = vec![vec![]; CQDB_NUM_TABLES];
+    let mut backlinks = Vec::with_capacity(values.len());
+    for (id, value) in values.iter().enumerate() {
+        let key = CString::new(value.as_bytes())?;
+        let key = key.as_bytes_with_nul();
+        let hash = unsafe { crfsuite_sys::hashlittle(key.as_ptr() as *const c_void, key.len(), 0) };
+        let offset = (bytes.len() - begin) as u32;
+        tables[hash as usize % CQDB_NUM_TABLES].push((hash, offset));
+        backlinks.push(offset);
+
+        put_u32(bytes, id as u32);
+        put_u32(bytes, key.len() as u32);
+        put_bytes(bytes, key);
+    }
+
+    // each table has twice as many buckets as records, filled with open addressing
+    let mut refs = Vec::with_capacity(CQDB_NUM_TABLES);
+    for table in &tables {
+        if table.is_empty() {
+            refs.push((0, 0));
+            continue;
+        }
+        let n = table.len() * 2;
+        let mut buckets = vec![(0, 0); n];
+        for &(hash, offset) in table {
+            let mut k = (hash >> 8) as usize % n;
+            while buckets[k].1 != 0 {
+                k = (k + 1) % n;
+            }
+            buckets[k] = (hash, offset);
+        }
+        refs.push(((bytes.len() - begin) as u32, n as u32));
+        for (hash, offset) in buckets {
+            put_u32(bytes, hash);
+            put_u32(bytes, offset);
+        }
+    }
+
+    let bwd_offset = if backlinks.is_empty() {
+        0
+    } else {
+        bytes.len() - begin
+    };
+    for offset in &backlinks {
+        put_u32(bytes, *offset);
+    }
+
+    let mut header = Vec::with_capacity(CQDB_OFFSET_DATA);
+    put_bytes(&mut header, b"CQDB");
+    put_u32(&mut header, (bytes.len() - begin) as u32);
+    // flag
+    put_u32(&mut header, 0);
+    put_u32(&mut header, CQDB_BYTEORDER_CHECK);
+    put_u32(&mut header, backlinks.len() as u32);
+    put_u32(&mut header, bwd_offset as u32);
+    for (offset, num) in refs {
+        put_u32(&mut header, offset);
+        put_u32(&mut header, num);
+    }
+    bytes[begin..begin + CQDB_OFFSET_DATA].copy_from_slice(&header);
+
+    Ok(())
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    bytes.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelWriter;
+    use crate::{Model, SimpleAttribute, Tagger};
+
+    #[test]
+    fn model_writer_works() {
+        let mut writer = ModelWriter::new();
+        writer.add_label("O");
+        writer.set_transition("O", "NUM", -1.0);
+        writer.set_transition("NUM", "NUM", 0.5);
+        writer.set_state_feature("is_digit", "NUM", 1.0);
+        // replaces the previous weight
+        writer.set_state_feature("is_digit", "NUM", 2.0);
+        writer.set_state_feature("is_digit", "O", -2.0);
+        writer.set_state_feature("is_word", "O", 2.0);
+
+        assert_eq!(writer.num_labels(), 2);
+        assert_eq!(writer.num_attrs(), 2);
+        assert_eq!(writer.num_features(), 5);
+
+        let bytes = writer.write_to_bytes().unwrap();
+
+        let model = Model::create_from_memory(bytes.clone()).unwrap();
+        assert_eq!(model.labels().collect::<Vec<_>>(), vec!["O", "NUM"]);
+        assert_eq!(
+            model.attributes().collect::<Vec<_>>(),
+            vec!["is_digit", "is_word"]
+        );
+        assert_eq!(
+            model.transitions().collect::<Vec<_>>(),
+            vec![("O", "NUM", -1.0), ("NUM", "NUM", 0.5)]
+        );
+        assert_eq!(
+            model.state_features().collect::<Vec<_>>(),
+            vec![
+                ("is_digit", "NUM", 2.0),
+                ("is_digit", "O", -2.0),
+                ("is_word", "O", 2.0)
+            ]
+        );
+
+        let tagger = Tagger::create_from_memory(bytes).unwrap();
+        let input = vec![
+            vec![SimpleAttribute {
+                attr: "is_word".to_string(),
+                value: 1.0,
+            }],
+            vec![SimpleAttribute {
+                attr: "is_digit".to_string(),
+                value: 1.0,
+            }],
+        ];
+        assert_eq!(tagger.tag(&input).unwrap(), vec!["O", "NUM"]);
+    }
+}